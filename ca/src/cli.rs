@@ -30,29 +30,37 @@ async fn clear_ca_directory(ca_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// How to trust the root certificate of the CA in `path`, for the CLI to show.
 #[cfg(target_os = "linux")]
-fn print_ca_instructions(path: &Path) {
+pub fn ca_instructions(path: &Path) -> String {
     let cert_path = path.join("myca.crt");
-    eprintln!("Root Certificate: {:#?}", cert_path);
-    eprintln!("To trust this certificate, run: ");
-    eprintln!(
-        "sudo cp {:#?} /usr/local/share/ca-certificates/extra",
-        cert_path
-    );
-    eprintln!("sudo update-ca-certificates");
-    eprintln!("To use in a browser, read more here: https://proxysaur.us/ca#trusting-the-root-certificate-in-your-browser");
+    [
+        format!("Root Certificate: {:#?}", cert_path),
+        "To trust this certificate, run: ".into(),
+        format!(
+            "sudo cp {:#?} /usr/local/share/ca-certificates/extra",
+            cert_path
+        ),
+        "sudo update-ca-certificates".into(),
+        "To use in a browser, read more here: https://proxysaur.us/ca#trusting-the-root-certificate-in-your-browser".into(),
+    ]
+    .join("\n")
 }
 
+/// How to trust the root certificate of the CA in `path`, for the CLI to show.
 #[cfg(target_os = "macos")]
-fn print_ca_instructions(path: &Path) {
+pub fn ca_instructions(path: &Path) -> String {
     let cert_path = path.join("myca.crt");
-    eprintln!("Root Certificate: {:#?}", cert_path);
-    eprintln!("To trust this certificate, run: ");
-    eprintln!(
-        "security add-trusted-cert -d -r trustRoot -k $HOME/Library/Keychains/login.keychain {:#?}",
-        cert_path
-    );
-    eprintln!("To use in a browser, read more here: https://proxysaur.us/ca#trusting-the-root-certificate-in-your-browser");
+    [
+        format!("Root Certificate: {:#?}", cert_path),
+        "To trust this certificate, run: ".into(),
+        format!(
+            "security add-trusted-cert -d -r trustRoot -k $HOME/Library/Keychains/login.keychain {:#?}",
+            cert_path
+        ),
+        "To use in a browser, read more here: https://proxysaur.us/ca#trusting-the-root-certificate-in-your-browser".into(),
+    ]
+    .join("\n")
 }
 
 pub async fn generate_ca(path: Option<PathBuf>, force_overwrite: bool) -> Result<PathBuf> {
@@ -79,13 +87,12 @@ pub async fn generate_ca(path: Option<PathBuf>, force_overwrite: bool) -> Result
             let before = cert.not_before();
 
             if now > after {
-                eprintln!("Expired certificate. Rebuilding directory.");
+                tracing::info!(?ca_dir, "Expired certificate. Rebuilding directory.");
                 clear_ca_directory(&ca_dir).await?;
             } else if now < before {
-                eprintln!("Certificate isn't active yet.");
+                tracing::warn!(?ca_dir, "Certificate isn't active yet.");
             } else {
-                eprintln!("Using existing CA dir: {:?}", ca_dir);
-                print_ca_instructions(&ca_dir);
+                tracing::debug!(?ca_dir, "Using existing CA dir.");
                 return Ok(ca_dir);
             }
         }
@@ -98,8 +105,8 @@ pub async fn generate_ca(path: Option<PathBuf>, force_overwrite: bool) -> Result
     let config = include_str!("scripts/config.conf");
     let script = include_str!("scripts/generateca.sh");
 
+    let script_path = ca_dir.join("generateca.sh");
     {
-        let script_path = ca_dir.join("generateca.sh");
         let config_path = ca_dir.join("config");
         tracing::debug!(?script_path, "Creating script file.");
         let mut file = tokio::fs::File::create(script_path.as_path()).await?;
@@ -107,7 +114,7 @@ pub async fn generate_ca(path: Option<PathBuf>, force_overwrite: bool) -> Result
             tracing::error!(%error, "Error writing script file.");
             error
         })?;
-        file.set_permissions(std::fs::Permissions::from_mode(0o777))
+        file.set_permissions(std::fs::Permissions::from_mode(0o700))
             .await?;
         tracing::debug!(?config, "Creating config file.");
         let mut file = tokio::fs::File::create(config_path.as_path()).await?;
@@ -115,13 +122,12 @@ pub async fn generate_ca(path: Option<PathBuf>, force_overwrite: bool) -> Result
             tracing::error!(%error, "Error writing config file.");
             error
         })?;
-
-        let path = std::env::var("PATH").unwrap_or_else(|_| "".to_string());
-        std::env::set_var("PATH", format!("{}:{}", path, ca_dir_str));
     }
 
-    let output = tokio::process::Command::new("generateca.sh")
+    // Run by its path, so the process' `PATH` is left alone
+    let output = tokio::process::Command::new(&script_path)
         .args([escape_path(ca_dir_str)])
+        .current_dir(&ca_dir)
         .output()
         .await
         .map_err(|error| {
@@ -133,8 +139,6 @@ pub async fn generate_ca(path: Option<PathBuf>, force_overwrite: bool) -> Result
         return Err(anyhow::Error::from(CaError::GenerateCertificate));
     }
 
-    print_ca_instructions(&ca_dir);
-
     Ok(ca_dir.to_path_buf())
 }
 
@@ -84,9 +84,8 @@ pub fn default_ca_dir() -> Result<PathBuf> {
 /// Holds a path to the script used to generate and sign certificates.
 #[derive(Clone)]
 pub struct CertificateAuthority {
-    #[allow(unused)]
-    project_dirs: ProjectDirs,
     ca_path: PathBuf,
+    script_path: PathBuf,
     config_cache: Arc<RwLock<HashMap<String, ServerConfig>>>,
 }
 
@@ -113,42 +112,40 @@ pub async fn valid_ca_directory(ca_path: &Path) -> bool {
 
 impl CertificateAuthority {
     /// Loads the `generatecert.sh` script which dynamically generates certificate requests.
+    /// The script is written to the CA directory, so nothing outside of it is touched.
     pub async fn load(ca_path: &Path) -> Result<Self> {
         let script = include_str!("scripts/generatecert.sh");
-        let project_dirs = init_project_dirs().await?;
 
         if !valid_ca_directory(ca_path).await {
             let msg = format!("{:?} is not a valid CA directory", ca_path);
             return Err(anyhow::Error::msg(msg));
         }
 
+        let script_path = ca_path.join("generatecert.sh");
         {
-            let script_dir_str = project_dirs
-                .data_dir()
-                .to_str()
-                .ok_or_else(|| CaError::CustomError("Error parsing script directory.".into()))?
-                .to_string();
-            let script_path = project_dirs.data_dir().join("generatecert.sh");
-            let mut file = tokio::fs::File::create(script_path).await?;
+            let mut file = tokio::fs::File::create(&script_path).await?;
             file.write(script.as_bytes()).await.map_err(|error| {
                 tracing::error!(%error, "Error writing script file.");
                 error
             })?;
-            file.set_permissions(std::fs::Permissions::from_mode(0o777))
+            file.set_permissions(std::fs::Permissions::from_mode(0o700))
                 .await?;
-
-            let path = std::env::var("PATH").unwrap_or_else(|_| "".to_string());
-            std::env::set_var("PATH", format!("{}:{}", path, script_dir_str));
         }
 
         let config_cache = Arc::new(RwLock::new(HashMap::new()));
 
         Ok(Self {
-            project_dirs,
             ca_path: ca_path.to_path_buf(),
+            script_path,
             config_cache,
         })
     }
+
+    /// The PEM encoded root certificate, which clients need to trust.
+    pub async fn certificate(&self) -> Result<Vec<u8>> {
+        let cert = tokio::fs::read(self.ca_path.join("myca.pem")).await?;
+        Ok(cert)
+    }
 }
 
 impl CertificateAuthority {
@@ -229,7 +226,7 @@ impl CertificateAuthority {
         }
 
         let port = port.to_string();
-        let output = tokio::process::Command::new("generatecert.sh")
+        let output = tokio::process::Command::new(&self.script_path)
             .args([host, port.as_str(), tls_ca_path_str])
             .output()
             .await
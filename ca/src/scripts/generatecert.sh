@@ -11,6 +11,14 @@ PORT=$2
 
 cd $3
 
+# IP literals need an IP subject alternative name rather than a DNS one
+if [[ $DOMAIN =~ ^[0-9.]+$ || $DOMAIN == *:* ]]
+then
+  SAN="IP.1 = $DOMAIN"
+else
+  SAN="DNS.1 = $DOMAIN"
+fi
+
 openssl genrsa -out $DOMAIN.key 2048
 openssl req -new -key $DOMAIN.key -out $DOMAIN.csr \
 -subj "/C=US/ST=NC/L=Asheville/O=prox/OU=proxy/CN=$DOMAIN"
@@ -21,7 +29,7 @@ basicConstraints=CA:FALSE
 keyUsage = digitalSignature, nonRepudiation, keyEncipherment, dataEncipherment
 subjectAltName = @alt_names
 [alt_names]
-DNS.1 = $DOMAIN
+$SAN
 EOF
 
 openssl x509 -req -in $DOMAIN.csr -CA myca.pem -CAkey myca.key -CAcreateserial \
@@ -2,7 +2,7 @@ use std::{io::Write, num::ParseIntError, path::PathBuf, str::FromStr};
 
 use anyhow::Result;
 
-use crate::{Config, Protocol, Proxy, ProxyBuilder};
+use crate::{Config, HookCapabilities, ModuleLimits, Protocol, Proxy, ProxyBuilder, StateOptions};
 
 /// Responsible for creating a proxysaur.toml file
 pub fn init(path: Option<PathBuf>) -> Result<PathBuf> {
@@ -121,6 +121,7 @@ fn get_proxy() -> Result<Proxy> {
     };
 
     builder
+        .name(None)
         .address(address)
         .port(port)
         .protocol(protocol)
@@ -132,6 +133,12 @@ fn get_proxy() -> Result<Proxy> {
         .response_wasi_module_path(response_wasi_module_path)
         .proxy_configuration_path(proxy_configuration_path)
         .wasi_configuration_bytes(None)
+        .module_limits(ModuleLimits::default())
+        .state(StateOptions::default())
+        .capabilities(HookCapabilities::default())
+        .pre_request_modules(vec![])
+        .request_modules(vec![])
+        .response_modules(vec![])
         .build()
         .map_err(anyhow::Error::from)
 }
@@ -48,6 +48,42 @@ pub enum Commands {
         #[clap(long, short)]
         port: Option<u16>,
     },
+    /// Runs a module against fixtures, without starting a proxy
+    TestModule {
+        /// Path to the WASM module
+        module: PathBuf,
+        /// The hook the module runs in [pre-request|request|response]
+        #[clap(long)]
+        hook: Hook,
+        /// Path to the module's configuration file, optional
+        #[clap(short, long)]
+        config_path: Option<PathBuf>,
+        /// JSON or YAML fixture files, or HAR files
+        #[clap(required = true)]
+        fixtures: Vec<PathBuf>,
+    },
+}
+
+/// The points in a request's life modules run at.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hook {
+    PreRequest,
+    Request,
+    Response,
+}
+
+impl FromStr for Hook {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pre-request" => Ok(Hook::PreRequest),
+            "request" => Ok(Hook::Request),
+            "response" => Ok(Hook::Response),
+            _ => Err(anyhow::Error::msg("Invalid hook.")),
+        }
+    }
 }
 
 fn default_address() -> String {
@@ -79,8 +115,161 @@ fn default_config() -> Option<Bytes> {
     None
 }
 
+/// What happens to a request or response when a module traps or exceeds one of its limits.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// Pass the original request or response through untouched
+    Open,
+    /// Return an error response with the trap message
+    Closed,
+}
+
+impl Default for FailurePolicy {
+    fn default() -> Self {
+        FailurePolicy::Closed
+    }
+}
+
+/// Resource limits applied to every module run by a proxy. Unset limits are unbounded.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleLimits {
+    /// Units of fuel a module may consume, roughly one per WebAssembly instruction
+    #[serde(default)]
+    pub fuel: Option<u64>,
+    /// Wall-clock time a module may run for, in milliseconds
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Maximum size of a module's linear memory, in bytes
+    #[serde(default)]
+    pub max_memory_bytes: Option<usize>,
+    /// Maximum number of elements in a module's tables
+    #[serde(default)]
+    pub max_table_elements: Option<u32>,
+    #[serde(default)]
+    pub failure_policy: FailurePolicy,
+}
+
+fn default_http_client_timeout_ms() -> u64 {
+    5000
+}
+
+/// Where a module may send requests through the `http-client` interface.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HttpClientPolicy {
+    /// Destinations as `host` or `host:port`. Nothing is allowed by default.
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    /// How long a request may take, including reading the body, in milliseconds
+    #[serde(default = "default_http_client_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl Default for HttpClientPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: vec![],
+            timeout_ms: default_http_client_timeout_ms(),
+        }
+    }
+}
+
+/// Whether a module can change what's in a preopened directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DirAccess {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl Default for DirAccess {
+    fn default() -> Self {
+        DirAccess::ReadOnly
+    }
+}
+
+/// A host directory made available to a module.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PreopenedDir {
+    pub host_path: PathBuf,
+    /// Where the module sees the directory
+    pub guest_path: String,
+    #[serde(default)]
+    pub access: DirAccess,
+}
+
+/// What a module's WASI environment exposes. Modules get nothing unless it's listed here.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleCapabilities {
+    #[serde(default)]
+    pub preopened_dirs: Vec<PreopenedDir>,
+    /// Names of the proxy's environment variables passed through to the module
+    #[serde(default)]
+    pub env: Vec<String>,
+    /// Arguments passed to the module after its name
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Whether the module's stdout and stderr are written to the proxy's
+    #[serde(default)]
+    pub stdio: bool,
+}
+
+/// The WASI capabilities of each of a proxy's modules.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookCapabilities {
+    #[serde(default)]
+    pub pre_request: ModuleCapabilities,
+    #[serde(default)]
+    pub request: ModuleCapabilities,
+    #[serde(default)]
+    pub response: ModuleCapabilities,
+}
+
+/// Options for the key-value state shared by a proxy's modules.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateOptions {
+    /// File the state is loaded from and saved to. State only lives in memory when unset.
+    #[serde(default)]
+    pub persist_path: Option<PathBuf>,
+}
+
+/// A module in one of a proxy's pipelines.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WasiModule {
+    pub path: PathBuf,
+    /// File the module's configuration is read from
+    #[serde(default)]
+    pub configuration_path: Option<PathBuf>,
+    #[serde(skip, default = "default_config")]
+    pub configuration_bytes: Option<Bytes>,
+    #[serde(default)]
+    pub http_client: HttpClientPolicy,
+}
+
+/// A Rhai script run in one of a proxy's hooks, after its modules.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    pub path: PathBuf,
+    #[serde(skip)]
+    pub source: Option<String>,
+}
+
+/// The script of each of a proxy's hooks.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookScripts {
+    #[serde(default)]
+    pub pre_request: Option<Script>,
+    #[serde(default)]
+    pub request: Option<Script>,
+    #[serde(default)]
+    pub response: Option<Script>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Builder)]
 pub struct Proxy {
+    /// A name for the listener, shown to pre-request modules
+    #[serde(default)]
+    pub name: Option<String>,
     #[serde(default)]
     pub pre_request_wasi_module_path: Option<PathBuf>,
     #[serde(default)]
@@ -98,6 +287,28 @@ pub struct Proxy {
     pub address: String,
     pub upstream_address: String,
     pub upstream_port: u16,
+    #[serde(default)]
+    pub module_limits: ModuleLimits,
+    #[serde(default)]
+    pub state: StateOptions,
+    #[serde(default)]
+    pub capabilities: HookCapabilities,
+    /// Modules run in order after `pre_request_wasi_module_path`
+    #[serde(default)]
+    pub pre_request_modules: Vec<WasiModule>,
+    /// Modules run in order after `request_wasi_module_path`
+    #[serde(default)]
+    pub request_modules: Vec<WasiModule>,
+    /// Modules run in order after `response_wasi_module_path`
+    #[serde(default)]
+    pub response_modules: Vec<WasiModule>,
+    /// Scripts run after each hook's modules
+    #[serde(default)]
+    pub scripts: HookScripts,
+    /// Logs what each module and script changes about the requests and responses passing
+    /// through it
+    #[serde(default)]
+    pub diff_hooks: bool,
 }
 
 impl Default for Proxy {
@@ -109,6 +320,7 @@ impl Default for Proxy {
 impl Proxy {
     pub fn new() -> Self {
         Self {
+            name: None,
             pre_request_wasi_module_path: None,
             request_wasi_module_path: None,
             response_wasi_module_path: None,
@@ -120,7 +332,112 @@ impl Proxy {
             address: "blah".into(),
             upstream_address: "blah".into(),
             upstream_port: 8080,
+            module_limits: ModuleLimits::default(),
+            state: StateOptions::default(),
+            capabilities: HookCapabilities::default(),
+            pre_request_modules: vec![],
+            request_modules: vec![],
+            response_modules: vec![],
+            scripts: HookScripts::default(),
+            diff_hooks: false,
+        }
+    }
+
+    /// The hook's own module, which gets the proxy's configuration, followed by its pipeline.
+    /// Only modules in the pipeline can be allowed to make outbound requests.
+    fn pipeline(&self, path: &Option<PathBuf>, modules: &[WasiModule]) -> Vec<WasiModule> {
+        path.iter()
+            .map(|path| WasiModule {
+                path: path.clone(),
+                configuration_path: self.proxy_configuration_path.clone(),
+                configuration_bytes: self.wasi_configuration_bytes.clone(),
+                http_client: HttpClientPolicy::default(),
+            })
+            .chain(modules.iter().cloned())
+            .collect()
+    }
+
+    pub fn pre_request_pipeline(&self) -> Vec<WasiModule> {
+        self.pipeline(
+            &self.pre_request_wasi_module_path,
+            &self.pre_request_modules,
+        )
+    }
+
+    pub fn request_pipeline(&self) -> Vec<WasiModule> {
+        self.pipeline(&self.request_wasi_module_path, &self.request_modules)
+    }
+
+    pub fn response_pipeline(&self) -> Vec<WasiModule> {
+        self.pipeline(&self.response_wasi_module_path, &self.response_modules)
+    }
+
+    fn pipeline_modules_mut(&mut self) -> impl Iterator<Item = &mut WasiModule> + '_ {
+        self.pre_request_modules
+            .iter_mut()
+            .chain(self.request_modules.iter_mut())
+            .chain(self.response_modules.iter_mut())
+    }
+
+    fn scripts_mut(&mut self) -> impl Iterator<Item = &mut Script> + '_ {
+        self.scripts
+            .pre_request
+            .iter_mut()
+            .chain(self.scripts.request.iter_mut())
+            .chain(self.scripts.response.iter_mut())
+    }
+
+    /// The paths of the proxy's scripts, which are reloaded when they change.
+    pub fn script_paths(&self) -> Vec<PathBuf> {
+        self.scripts
+            .pre_request
+            .iter()
+            .chain(self.scripts.request.iter())
+            .chain(self.scripts.response.iter())
+            .map(|script| script.path.clone())
+            .collect()
+    }
+
+    /// Reads the configuration of the proxy and of every module in its pipelines, along with
+    /// its scripts.
+    pub fn load_configuration(&mut self) -> Result<()> {
+        if let Some(config_path) = self.proxy_configuration_path.as_ref() {
+            let contents = std::fs::read(config_path)?;
+            self.wasi_configuration_bytes = Some(Bytes::from(contents));
+        }
+        for module in self.pipeline_modules_mut() {
+            if let Some(config_path) = module.configuration_path.as_ref() {
+                let contents = std::fs::read(config_path)?;
+                module.configuration_bytes = Some(Bytes::from(contents));
+            }
+        }
+        for script in self.scripts_mut() {
+            script.source = Some(std::fs::read_to_string(&script.path)?);
+        }
+        Ok(())
+    }
+
+    /// Replaces the configuration or script read from `path` wherever it's used, returning
+    /// whether it was used at all.
+    pub fn update_configuration(&mut self, path: &Path, contents: Bytes) -> bool {
+        let mut used = false;
+        if self.proxy_configuration_path.as_deref() == Some(path) {
+            self.wasi_configuration_bytes = Some(contents.clone());
+            used = true;
         }
+        for module in self.pipeline_modules_mut() {
+            if module.configuration_path.as_deref() == Some(path) {
+                module.configuration_bytes = Some(contents.clone());
+                used = true;
+            }
+        }
+        for script in self.scripts_mut() {
+            if script.path == path {
+                script.source = Some(String::from_utf8_lossy(&contents).into_owned());
+                used = true;
+            }
+        }
+        used
     }
 
     pub fn address(&self) -> String {
@@ -134,6 +451,11 @@ impl Proxy {
         addr
     }
 
+    /// The listener name, falling back to the address it's bound to.
+    pub fn listener_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.address())
+    }
+
     pub fn upstream_address(&self) -> String {
         let mut addr = self.upstream_address.clone();
         addr.push(':');
@@ -184,11 +506,8 @@ impl TryFrom<&Path> for Config {
         let contents = std::fs::read(path)?;
         let mut config: Config = toml::from_slice(&contents).map_err(anyhow::Error::from)?;
 
-        for mut proxy in config.proxy.iter_mut() {
-            if let Some(config_path) = proxy.proxy_configuration_path.as_ref() {
-                let contents = std::fs::read(config_path)?;
-                proxy.wasi_configuration_bytes = Some(Bytes::from(contents));
-            }
+        for proxy in config.proxy.iter_mut() {
+            proxy.load_configuration()?;
         }
 
         Ok(config)
@@ -211,10 +530,18 @@ impl TryFrom<Args> for Config {
 
 #[cfg(test)]
 mod test {
-    use std::{fs::File, io::Write, path::PathBuf};
+    use bytes::Bytes;
+    use std::{
+        fs::File,
+        io::Write,
+        path::{Path, PathBuf},
+    };
     use tempdir::TempDir;
 
-    use super::{Args, Config, Protocol};
+    use super::{
+        Args, Config, DirAccess, FailurePolicy, HttpClientPolicy, ModuleCapabilities, PreopenedDir,
+        Protocol, StateOptions,
+    };
 
     fn tests() -> (TempDir, PathBuf) {
         let data = include_bytes!("tests/config.toml");
@@ -243,6 +570,49 @@ mod test {
         assert_eq!(&config.proxy[0].upstream_address(), "127.0.0.1:5432");
         assert_eq!(&config.proxy[1].upstream_address(), "127.0.0.1:8000");
         assert_eq!(&config.proxy[2].upstream_address(), "127.0.0.1:8001");
+        assert_eq!(
+            config.proxy[0].module_limits.failure_policy,
+            FailurePolicy::Closed
+        );
+        let limits = &config.proxy[1].module_limits;
+        assert_eq!(limits.fuel, Some(1_000_000));
+        assert_eq!(limits.timeout_ms, Some(250));
+        assert_eq!(limits.max_memory_bytes, Some(16_777_216));
+        assert_eq!(limits.max_table_elements, None);
+        assert_eq!(limits.failure_policy, FailurePolicy::Open);
+        let pipeline = config.proxy[1].request_pipeline();
+        assert_eq!(pipeline[0].http_client, HttpClientPolicy::default());
+        assert_eq!(pipeline[1].path, PathBuf::from("/tmp/token.wasm"));
+        assert_eq!(
+            pipeline[1].http_client.allowed_hosts,
+            vec!["localhost:9000"]
+        );
+        assert_eq!(pipeline[1].http_client.timeout_ms, 1000);
+        assert_eq!(
+            config.proxy[1].state.persist_path,
+            Some(PathBuf::from("/tmp/proxysaur-state.json"))
+        );
+        assert_eq!(config.proxy[0].state, StateOptions::default());
+        let request = &config.proxy[1].capabilities.request;
+        assert_eq!(
+            request.preopened_dirs,
+            vec![PreopenedDir {
+                host_path: PathBuf::from("/tmp/fixtures"),
+                guest_path: "/fixtures".into(),
+                access: DirAccess::ReadOnly,
+            }]
+        );
+        assert_eq!(request.env, vec!["API_TOKEN"]);
+        assert!(request.args.is_empty());
+        assert!(!request.stdio);
+        assert_eq!(
+            config.proxy[1].capabilities.response.preopened_dirs[0].access,
+            DirAccess::ReadWrite
+        );
+        assert_eq!(
+            config.proxy[0].capabilities.pre_request,
+            ModuleCapabilities::default()
+        );
     }
 
     #[test]
@@ -259,4 +629,99 @@ mod test {
         let config = config.expect("should parse the config");
         assert_eq!(config.proxy.len(), 3);
     }
+
+    #[test]
+    fn loads_pipeline_configuration() {
+        let tmp_dir = TempDir::new("proxysaur").expect("should create the temp dir");
+        let auth_config = tmp_dir.path().join("auth.yml");
+        std::fs::write(&auth_config, "token: abc").expect("should write the configuration");
+        let config_path = tmp_dir.path().join("proxysaur.toml");
+        let contents = format!(
+            r#"
+[[proxy]]
+request_wasi_module_path = "/tmp/mock.wasm"
+upstream_address = "127.0.0.1"
+upstream_port = 8000
+protocol = "http"
+tls = false
+
+[[proxy.request_modules]]
+path = "/tmp/auth.wasm"
+configuration_path = {auth_config:?}
+
+[[proxy.request_modules]]
+path = "/tmp/log.wasm"
+"#
+        );
+        std::fs::write(&config_path, contents).expect("should write the config");
+
+        let mut config = Config::try_from(config_path.as_path()).expect("should parse the config");
+        let proxy = &mut config.proxy[0];
+        let pipeline = proxy.request_pipeline();
+        let paths: Vec<&str> = pipeline
+            .iter()
+            .map(|module| module.path.to_str().unwrap_or_default())
+            .collect();
+        assert_eq!(
+            paths,
+            vec!["/tmp/mock.wasm", "/tmp/auth.wasm", "/tmp/log.wasm"]
+        );
+        assert_eq!(
+            pipeline[1].configuration_bytes,
+            Some(Bytes::from("token: abc"))
+        );
+        assert_eq!(pipeline[2].configuration_bytes, None);
+        assert!(proxy.pre_request_pipeline().is_empty());
+
+        assert!(proxy.update_configuration(&auth_config, Bytes::from("token: def")));
+        assert_eq!(
+            proxy.request_pipeline()[1].configuration_bytes,
+            Some(Bytes::from("token: def"))
+        );
+        assert!(!proxy.update_configuration(Path::new("/tmp/other.yml"), Bytes::new()));
+    }
+
+    #[test]
+    fn loads_scripts() {
+        let tmp_dir = TempDir::new("proxysaur").expect("should create the temp dir");
+        let script_path = tmp_dir.path().join("mock.rhai");
+        std::fs::write(&script_path, "request.set_header(\"x-mock\", \"1\");")
+            .expect("should write the script");
+        let config_path = tmp_dir.path().join("proxysaur.toml");
+        let contents = format!(
+            r#"
+[[proxy]]
+upstream_address = "127.0.0.1"
+upstream_port = 8000
+protocol = "http"
+tls = false
+
+[proxy.scripts.request]
+path = {script_path:?}
+"#
+        );
+        std::fs::write(&config_path, contents).expect("should write the config");
+
+        let mut config = Config::try_from(config_path.as_path()).expect("should parse the config");
+        let proxy = &mut config.proxy[0];
+        assert_eq!(proxy.script_paths(), vec![script_path.clone()]);
+        assert!(proxy.scripts.pre_request.is_none());
+        let script = proxy
+            .scripts
+            .request
+            .as_ref()
+            .expect("should have a script");
+        assert_eq!(
+            script.source.as_deref(),
+            Some("request.set_header(\"x-mock\", \"1\");")
+        );
+
+        assert!(proxy.update_configuration(&script_path, Bytes::from("request.set_body(\"\");")));
+        let script = proxy
+            .scripts
+            .request
+            .as_ref()
+            .expect("should have a script");
+        assert_eq!(script.source.as_deref(), Some("request.set_body(\"\");"));
+    }
 }
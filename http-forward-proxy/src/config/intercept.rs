@@ -1,9 +1,14 @@
 use crate::config::{
-    redirect::RequestRedirect,
+    redirect::{deserialize_redirects, RequestRedirect},
     rewrite::{RequestRewrite, ResponseRewrite},
 };
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use regex::Regex;
+use serde::{
+    de::{self, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{fmt, str::FromStr};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HostConfig {
@@ -12,7 +17,9 @@ pub struct HostConfig {
     pub response_rewrites: Vec<ResponseRewrite>,
     #[serde(default = "default_req_rewrite")]
     pub request_rewrites: Vec<RequestRewrite>,
-    pub redirect: Option<RequestRedirect>,
+    /// Evaluated in order, the first that matches redirects the request
+    #[serde(default, deserialize_with = "deserialize_redirects")]
+    pub redirect: Vec<RequestRedirect>,
 }
 
 fn default_resp_rewrite() -> Vec<ResponseRewrite> {
@@ -23,19 +30,220 @@ fn default_req_rewrite() -> Vec<RequestRewrite> {
     vec![]
 }
 
+/// What a key of `hosts` matches, besides the port.
+#[derive(Debug, Clone)]
+enum HostPattern {
+    /// `example.com`
+    Exact(String),
+    /// `*.example.com`, kept as `.example.com`
+    Suffix(String),
+    /// `~^api-[0-9]+\.internal$`, or a wildcard elsewhere in the host like
+    /// `api-*.staging.internal`
+    Regex(Regex),
+}
+
+/// A key of `hosts`, optionally limited to a port with `host:port`.
+#[derive(Debug, Clone)]
+struct HostKey {
+    pattern: HostPattern,
+    port: Option<u16>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum HostKeyError {
+    #[error("invalid host regex {0:?}: {1}")]
+    InvalidRegex(String, regex::Error),
+}
+
+/// Splits the port off `host:port`, leaving IPv6 literals like `[::1]` alone.
+fn split_port(key: &str) -> (&str, Option<u16>) {
+    match key.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => {
+            match port.parse::<u16>() {
+                Ok(port) => (host, Some(port)),
+                Err(_) => (key, None),
+            }
+        }
+        _ => (key, None),
+    }
+}
+
+/// Hosts are matched without regard to case or a trailing dot.
+fn normalize_host(host: &str) -> String {
+    host.trim_end_matches('.').to_lowercase()
+}
+
+impl FromStr for HostKey {
+    type Err = HostKeyError;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        if let Some(regex) = key.strip_prefix('~') {
+            let regex = Regex::new(regex)
+                .map_err(|err| HostKeyError::InvalidRegex(regex.to_string(), err))?;
+            return Ok(Self {
+                pattern: HostPattern::Regex(regex),
+                port: None,
+            });
+        }
+
+        let (host, port) = split_port(key);
+        let host = normalize_host(host);
+        let pattern = match host.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') && !suffix.contains('*') => {
+                HostPattern::Suffix(suffix.to_string())
+            }
+            _ if host.contains('*') => {
+                // Wildcards in the middle of a host stay within a label
+                let pattern = host
+                    .split('*')
+                    .map(regex::escape)
+                    .collect::<Vec<String>>()
+                    .join("[^.]*");
+                let regex = Regex::new(&format!("^{pattern}$"))
+                    .map_err(|err| HostKeyError::InvalidRegex(host.clone(), err))?;
+                HostPattern::Regex(regex)
+            }
+            _ => HostPattern::Exact(host),
+        };
+        Ok(Self { pattern, port })
+    }
+}
+
+impl HostKey {
+    fn matches_port(&self, port: Option<u16>) -> bool {
+        self.port.map_or(true, |key_port| Some(key_port) == port)
+    }
+
+    fn is_exact(&self, hostname: &str) -> bool {
+        matches!(&self.pattern, HostPattern::Exact(host) if host == hostname)
+    }
+
+    /// The length of the suffix when it matches, since longer suffixes are more specific.
+    fn suffix_len(&self, hostname: &str) -> Option<usize> {
+        match &self.pattern {
+            HostPattern::Suffix(suffix) if hostname.ends_with(suffix.as_str()) => {
+                Some(suffix.len())
+            }
+            _ => None,
+        }
+    }
+
+    fn is_regex_match(&self, hostname: &str) -> bool {
+        matches!(&self.pattern, HostPattern::Regex(regex) if regex.is_match(hostname))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct HostEntry {
+    key: String,
+    host_key: HostKey,
+    config: HostConfig,
+}
+
+fn serialize_hosts<S: Serializer>(hosts: &[HostEntry], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(hosts.len()))?;
+    for entry in hosts.iter() {
+        map.serialize_entry(&entry.key, &entry.config)?;
+    }
+    map.end()
+}
+
+/// Keeps the hosts in the order they're written in, since regular expressions are tried in
+/// that order.
+fn deserialize_hosts<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<HostEntry>, D::Error> {
+    struct HostsVisitor;
+
+    impl<'de> Visitor<'de> for HostsVisitor {
+        type Value = Vec<HostEntry>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a map of hosts to their configuration")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut hosts = vec![];
+            while let Some((key, config)) = map.next_entry::<String, HostConfig>()? {
+                let host_key = HostKey::from_str(&key).map_err(de::Error::custom)?;
+                hosts.push(HostEntry {
+                    key,
+                    host_key,
+                    config,
+                });
+            }
+            Ok(hosts)
+        }
+    }
+
+    deserializer.deserialize_map(HostsVisitor)
+}
+
+/// The port of a request to `authority`, falling back to the default port of `scheme`.
+pub fn request_port(authority: &str, scheme: &str) -> Option<u16> {
+    match split_port(authority) {
+        (_host, Some(port)) => Some(port),
+        (_host, None) => match scheme {
+            "https" => Some(443),
+            "http" => Some(80),
+            _ => None,
+        },
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InterceptConfig {
-    hosts: HashMap<String, HostConfig>,
+    /// Hosts are keyed by their name, `*.` followed by a domain for any of its subdomains, or `~`
+    /// followed by a regular expression. Any but the last can be limited to a port with
+    /// `host:port`.
+    #[serde(
+        serialize_with = "serialize_hosts",
+        deserialize_with = "deserialize_hosts"
+    )]
+    hosts: Vec<HostEntry>,
 }
 
 impl InterceptConfig {
-    /// Fetches the configuration for interception & rewriting associated with the host.
-    pub fn host_config(&self, hostname: &str) -> Option<&HostConfig> {
-        self.hosts.get(hostname)
+    /// Fetches the configuration for interception & rewriting associated with the host. Exact
+    /// hosts take precedence over the longest matching suffix, which takes precedence over the
+    /// first matching regular expression. Keys with a matching port beat those without one.
+    pub fn host_config(&self, hostname: &str, port: Option<u16>) -> Option<&HostConfig> {
+        let hostname = normalize_host(hostname);
+        let candidates = self
+            .hosts
+            .iter()
+            .filter(|entry| entry.host_key.matches_port(port));
+
+        let exact = candidates
+            .clone()
+            .filter(|entry| entry.host_key.is_exact(&hostname))
+            .max_by_key(|entry| entry.host_key.port.is_some());
+        // Reversed so the first of equally long suffixes wins
+        let suffix = || {
+            candidates
+                .clone()
+                .rev()
+                .filter_map(|entry| {
+                    let len = entry.host_key.suffix_len(&hostname)?;
+                    Some(((len, entry.host_key.port.is_some()), entry))
+                })
+                .max_by_key(|(specificity, _entry)| *specificity)
+                .map(|(_specificity, entry)| entry)
+        };
+        let regex = || {
+            candidates
+                .clone()
+                .find(|entry| entry.host_key.is_regex_match(&hostname))
+        };
+
+        exact
+            .or_else(suffix)
+            .or_else(regex)
+            .map(|entry| &entry.config)
     }
 
-    pub fn should_intercept(&self, hostname: &str) -> bool {
-        self.hosts.contains_key(hostname)
+    pub fn should_intercept(&self, hostname: &str, port: Option<u16>) -> bool {
+        self.host_config(hostname, port).is_some()
     }
 }
 
@@ -91,7 +299,6 @@ mod test {
 
     #[test]
     fn test_deserialize() {
-        let mut hosts = HashMap::new();
         let req_rewrite = RequestRewrite {
             when: vec![RuleMatch::PathMatch(MatchValue::Exact("/".into()))],
             rewrite: Rewrite::Header(HeaderRewrite {
@@ -110,26 +317,150 @@ mod test {
                 new_status: "200".into(),
             }),
         };
-        hosts.insert(
-            "test.com".into(),
-            HostConfig {
+        let hosts = vec![HostEntry {
+            key: "test.com".into(),
+            host_key: HostKey::from_str("test.com").expect("should parse the key"),
+            config: HostConfig {
                 scheme: "https".into(),
                 response_rewrites: vec![resp_rewrite],
                 request_rewrites: vec![req_rewrite],
-                redirect: None,
+                redirect: vec![],
             },
-        );
+        }];
         let config = InterceptConfig { hosts };
-        serde_yaml::to_string(&config).expect("should deserialize");
+        let yaml = serde_yaml::to_string(&config).expect("should deserialize");
+        assert!(yaml.contains("test.com:"));
     }
 
     #[test]
     fn test_serialize() {
         let config: InterceptConfig = serde_yaml::from_str(CONFIG).expect("should serialize");
         let host = config
-            .hosts
-            .get("test.com")
+            .host_config("test.com", Some(443))
             .expect("should contain the key");
         assert_eq!(host.response_rewrites.len(), 1);
     }
+
+    #[test]
+    fn parses_redirect_lists() {
+        let config: InterceptConfig = serde_yaml::from_str(CONFIG).expect("should parse");
+        let host = config
+            .host_config("test3.com", None)
+            .expect("should contain the key");
+        assert_eq!(host.redirect.len(), 1);
+        let host = config
+            .host_config("test.com", None)
+            .expect("should contain the key");
+        assert!(host.redirect.is_empty());
+
+        let config: InterceptConfig = serde_yaml::from_str(
+            r#"
+            hosts:
+              foo.com:
+                scheme: https
+                redirect:
+                  - when:
+                      - path:
+                          regex: ^/old/(?P<rest>.*)$
+                    to:
+                      location:
+                        url: https://foo.com/new/$rest
+                        status: 301
+                  - to:
+                      url:
+                        url: https://staging.foo.com
+                        replace_path_and_query: true
+            "#,
+        )
+        .expect("should parse");
+        let host = config
+            .host_config("foo.com", None)
+            .expect("should contain the key");
+        assert_eq!(host.redirect.len(), 2);
+    }
+
+    fn scheme_for(config: &InterceptConfig, host: &str, port: Option<u16>) -> Option<String> {
+        config
+            .host_config(host, port)
+            .map(|config| config.scheme.clone())
+    }
+
+    #[test]
+    fn resolves_host_patterns() {
+        let config: InterceptConfig = serde_yaml::from_str(
+            r#"
+            hosts:
+              "~^api-[0-9]+\\.example\\.com$":
+                scheme: regex
+              api-*.staging.internal:
+                scheme: glob
+              "*.example.com":
+                scheme: suffix
+              "*.cdn.example.com":
+                scheme: longer-suffix
+              "*.cdn.example.com:8443":
+                scheme: suffix-with-port
+              www.example.com:
+                scheme: exact
+              www.example.com:8080:
+                scheme: exact-with-port
+              "~.*":
+                scheme: catch-all
+            "#,
+        )
+        .expect("should parse the config");
+
+        assert_eq!(
+            scheme_for(&config, "WWW.example.com.", Some(443)).as_deref(),
+            Some("exact")
+        );
+        assert_eq!(
+            scheme_for(&config, "www.example.com", Some(8080)).as_deref(),
+            Some("exact-with-port")
+        );
+        assert_eq!(
+            scheme_for(&config, "img.cdn.example.com", Some(443)).as_deref(),
+            Some("longer-suffix")
+        );
+        assert_eq!(
+            scheme_for(&config, "img.cdn.example.com", Some(8443)).as_deref(),
+            Some("suffix-with-port")
+        );
+        assert_eq!(
+            scheme_for(&config, "api-1.example.com", Some(443)).as_deref(),
+            Some("suffix")
+        );
+        assert_eq!(
+            scheme_for(&config, "example.com", Some(443)).as_deref(),
+            Some("catch-all")
+        );
+        assert_eq!(
+            scheme_for(&config, "api-eu.staging.internal", None).as_deref(),
+            Some("glob")
+        );
+        assert_eq!(
+            scheme_for(&config, "api-eu.db.staging.internal", None).as_deref(),
+            Some("catch-all")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_regexes() {
+        let config: Result<InterceptConfig, _> = serde_yaml::from_str(
+            r#"
+            hosts:
+              "~api-(":
+                scheme: https
+            "#,
+        );
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn finds_request_ports() {
+        assert_eq!(request_port("example.com:8080", "https"), Some(8080));
+        assert_eq!(request_port("example.com", "https"), Some(443));
+        assert_eq!(request_port("[::1]", "http"), Some(80));
+        assert_eq!(request_port("[::1]:3000", "http"), Some(3000));
+    }
 }
@@ -1,10 +1,13 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
 
 use http::Uri;
 use proxysaur_bindings::http::{request::HttpRequestResult as HttpRequest, response::HttpResponse};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::config::rewrite::RuleMatch;
+use crate::config::rewrite::{expand_captures, path_without_query, percent_decode, RuleMatch};
 
 fn serialize_uri<S: Serializer>(uri: &Uri, serializer: S) -> Result<S::Ok, S::Error> {
     let s: String = format!("{}", uri);
@@ -32,10 +35,12 @@ pub struct UrlDestination {
     pub replace_path_and_query: bool,
 }
 
+/// Serves local files instead of the upstream response. The request module needs `path` in one
+/// of its preopened directories.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FileDestination {
     pub path: PathBuf,
-    /// Whether or not to interpret root paths as index.{content_type}
+    /// Whether or not to interpret root paths, and other directories, as index files
     pub root_index: bool,
     /// If specified, overwrite the path of the request, for example:
     /// https://google.com/path/a -> /usr/local/google.com/path/a
@@ -43,8 +48,9 @@ pub struct FileDestination {
     /// An optional string to append to the end of the file, ex. for ".html"
     /// https://google.com/path/a -> /usr/local/google.com/path/a.html
     pub file_suffix: Option<String>,
-    /// The content type of the file
-    pub content_type: String,
+    /// The content type of the file, inferred from its extension when unset
+    #[serde(default)]
+    pub content_type: Option<String>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -53,52 +59,145 @@ pub enum FileDestinationError {
     IoError(#[from] std::io::Error),
 }
 
+/// The content type for a file extension, for the kinds of files usually mapped locally.
+fn infer_content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html; charset=UTF-8",
+        Some("css") => "text/css; charset=UTF-8",
+        Some("js" | "mjs") => "text/javascript; charset=UTF-8",
+        Some("json" | "map") => "application/json",
+        Some("txt") => "text/plain; charset=UTF-8",
+        Some("csv") => "text/csv; charset=UTF-8",
+        Some("xml") => "application/xml",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        Some("pdf") => "application/pdf",
+        Some("wasm") => "application/wasm",
+        Some("woff") => "font/woff",
+        Some("woff2") => "font/woff2",
+        Some("mp3") => "audio/mpeg",
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The byte range a `Range` header asks for, as an inclusive start and end.
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    Partial(usize, usize),
+    Unsatisfiable,
+}
+
+/// Only single ranges are served, anything else gets the whole file.
+fn byte_range(range: Option<&str>, len: usize) -> ByteRange {
+    let range = match range.and_then(|range| range.trim().strip_prefix("bytes=")) {
+        Some(range) if !range.contains(',') => range.trim(),
+        _ => return ByteRange::Full,
+    };
+    let (start, end) = match range.split_once('-') {
+        Some(bounds) => bounds,
+        None => return ByteRange::Full,
+    };
+    let (start, end) = match (start.parse::<usize>(), end.parse::<usize>()) {
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        // The last `end` bytes
+        (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        _ => return ByteRange::Full,
+    };
+    if start >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(start, end)
+    }
+}
+
+fn request_header<'a>(req: &'a HttpRequest, header: &http::header::HeaderName) -> Option<&'a str> {
+    req.headers
+        .iter()
+        .find(|(name, _value)| name.eq_ignore_ascii_case(header.as_str()))
+        .map(|(_name, value)| value.as_str())
+}
+
 impl FileDestination {
-    pub fn path_for_request(&self, req: &HttpRequest) -> PathBuf {
+    /// The file for the request, or `None` when its path would leave `path`.
+    pub fn path_for_request(&self, req: &HttpRequest) -> Option<PathBuf> {
         let mut path = self.path.clone();
 
         if !self.replace_path {
-            return path;
+            return Some(path);
         }
 
-        let req_path = if req.path.starts_with('/') {
-            &req.path[1..]
-        } else {
-            req.path.as_str()
-        };
-        path = path.join(req_path);
+        let req_path = path_without_query(&req.path);
+        for segment in req_path.split('/') {
+            // Decoded, so `%2e%2e` can't be used to leave `path` either
+            let segment = percent_decode(segment);
+            match segment.as_str() {
+                "" | "." => {}
+                ".." => return None,
+                segment if segment.contains(|c| c == '/' || c == '\\' || c == '\0') => return None,
+                segment => path.push(segment),
+            }
+        }
 
-        if self.root_index && req.path.ends_with('/') {
-            path = path.join("index");
+        if self.root_index && req_path.ends_with('/') {
+            return Some(self.index_for_dir(&path));
         }
 
         if let Some(suffix) = &self.file_suffix {
-            if let Some(Some(file_name)) = path.file_name().map(|f| f.to_str()) {
-                let mut new_file_name = String::from(file_name);
-                new_file_name.push_str(suffix);
-                path.set_file_name(new_file_name);
+            // Appending to `path` itself would name a sibling of it
+            if path != self.path {
+                if let Some(Some(file_name)) = path.file_name().map(|f| f.to_str()) {
+                    let mut new_file_name = String::from(file_name);
+                    new_file_name.push_str(suffix);
+                    path.set_file_name(new_file_name);
+                }
             }
         }
-        path
+        Some(path)
     }
 
-    /// Returns a response with the file, if it exists and is readable.
-    pub fn resp(&self, req: &HttpRequest) -> Result<HttpResponse, FileDestinationError> {
-        let path = self.path_for_request(req);
-        let contents = std::fs::read(path).map_err(FileDestinationError::from)?;
-        Ok(HttpResponse {
-            headers: vec![
-                (
-                    http::header::CONTENT_TYPE.to_string(),
-                    self.content_type.clone(),
-                ),
-                (
-                    http::header::CONTENT_LENGTH.to_string(),
-                    contents.len().to_string(),
-                ),
-            ],
-            status: 200,
-            body: contents,
+    /// The index file of a directory: `index` with the file suffix, or `index.html` without
+    /// one.
+    fn index_for_dir(&self, dir: &Path) -> PathBuf {
+        match &self.file_suffix {
+            Some(suffix) => dir.join(format!("index{suffix}")),
+            None => dir.join("index.html"),
+        }
+    }
+
+    fn response(
+        &self,
+        req: &HttpRequest,
+        status: u16,
+        mut headers: Vec<(String, String)>,
+        body: Vec<u8>,
+    ) -> HttpResponse {
+        headers.push((
+            http::header::CONTENT_LENGTH.to_string(),
+            body.len().to_string(),
+        ));
+        let body = if req.method.eq_ignore_ascii_case("HEAD") {
+            vec![]
+        } else {
+            body
+        };
+        HttpResponse {
+            headers,
+            status,
+            body,
             request_path: req.path.clone(),
             request_authority: req.authority.clone(),
             request_host: req.host.clone(),
@@ -106,7 +205,143 @@ impl FileDestination {
             request_version: req.version.clone(),
             request_headers: req.headers.clone(),
             request_method: req.method.clone(),
-        })
+        }
+    }
+
+    fn not_found(&self, req: &HttpRequest) -> HttpResponse {
+        let headers = vec![(
+            http::header::CONTENT_TYPE.to_string(),
+            "text/plain; charset=UTF-8".to_string(),
+        )];
+        self.response(req, 404, headers, b"Not Found".to_vec())
+    }
+
+    /// Returns a response with the file, or a 404 when there isn't one.
+    pub fn resp(&self, req: &HttpRequest) -> Result<HttpResponse, FileDestinationError> {
+        let mut path = match self.path_for_request(req) {
+            Some(path) => path,
+            None => return Ok(self.not_found(req)),
+        };
+        let mut metadata = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(self.not_found(req))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if metadata.is_dir() {
+            if !self.root_index {
+                return Ok(self.not_found(req));
+            }
+            path = self.index_for_dir(&path);
+            metadata = match std::fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() => metadata,
+                Ok(_metadata) => return Ok(self.not_found(req)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Ok(self.not_found(req))
+                }
+                Err(err) => return Err(err.into()),
+            };
+        }
+
+        let modified = metadata.modified().ok();
+        let modified_secs = modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_secs());
+        let etag = format!(
+            "\"{:x}-{:x}\"",
+            metadata.len(),
+            modified_secs.unwrap_or_default()
+        );
+        let mut headers = vec![
+            (http::header::ETAG.to_string(), etag.clone()),
+            (http::header::ACCEPT_RANGES.to_string(), "bytes".to_string()),
+        ];
+        if let Some(modified) = modified {
+            headers.push((
+                http::header::LAST_MODIFIED.to_string(),
+                httpdate::fmt_http_date(modified),
+            ));
+        }
+
+        let not_modified = match request_header(req, &http::header::IF_NONE_MATCH) {
+            Some(if_none_match) => if_none_match
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag),
+            None => request_header(req, &http::header::IF_MODIFIED_SINCE)
+                .and_then(|since| httpdate::parse_http_date(since).ok())
+                .and_then(|since| since.duration_since(UNIX_EPOCH).ok())
+                .zip(modified_secs)
+                .map_or(false, |(since, modified)| modified <= since.as_secs()),
+        };
+        if not_modified {
+            let mut resp = self.response(req, 304, headers, vec![]);
+            resp.headers
+                .retain(|(name, _value)| name != http::header::CONTENT_LENGTH.as_str());
+            return Ok(resp);
+        }
+
+        let contents = std::fs::read(&path).map_err(FileDestinationError::from)?;
+        let content_type = self
+            .content_type
+            .clone()
+            .unwrap_or_else(|| infer_content_type(&path).to_string());
+        headers.push((http::header::CONTENT_TYPE.to_string(), content_type));
+
+        // A range of a file that changed since the client's copy is useless to it
+        let range = match request_header(req, &http::header::IF_RANGE) {
+            Some(if_range) if if_range.trim() != etag => None,
+            _ => request_header(req, &http::header::RANGE),
+        };
+        match byte_range(range, contents.len()) {
+            ByteRange::Full => Ok(self.response(req, 200, headers, contents)),
+            ByteRange::Partial(start, end) => {
+                headers.push((
+                    http::header::CONTENT_RANGE.to_string(),
+                    format!("bytes {start}-{end}/{}", contents.len()),
+                ));
+                let body = contents[start..=end].to_vec();
+                Ok(self.response(req, 206, headers, body))
+            }
+            ByteRange::Unsatisfiable => {
+                headers.push((
+                    http::header::CONTENT_RANGE.to_string(),
+                    format!("bytes */{}", contents.len()),
+                ));
+                Ok(self.response(req, 416, headers, vec![]))
+            }
+        }
+    }
+}
+
+/// Answers with a redirect for the client to follow, instead of rerouting the request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocationDestination {
+    /// The `Location` of the redirect. `$name`, `${name}` and `$1` refer to capture groups of
+    /// the `when` matchers, for example `https://duckduckgo.com/$rest`
+    pub url: String,
+    /// One of 301, 302, 303, 307 or 308
+    #[serde(
+        default = "default_redirect_status",
+        deserialize_with = "deserialize_redirect_status"
+    )]
+    pub status: u16,
+}
+
+fn default_redirect_status() -> u16 {
+    302
+}
+
+fn deserialize_redirect_status<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u16, D::Error> {
+    let status = u16::deserialize(deserializer)?;
+    match status {
+        301 | 302 | 303 | 307 | 308 => Ok(status),
+        status => Err(serde::de::Error::custom(format!(
+            "{status} is not a redirect status"
+        ))),
     }
 }
 
@@ -116,6 +351,8 @@ pub enum RedirectDestination {
     File(FileDestination),
     #[serde(rename = "url")]
     Url(UrlDestination),
+    #[serde(rename = "location")]
+    Location(LocationDestination),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -162,13 +399,54 @@ impl RequestRedirect {
             .all(|when: &RuleMatch| when.matches(req))
     }
 
+    /// The capture groups of the `when` matchers, in order.
+    pub fn captures(&self, req: &HttpRequest) -> Vec<(String, String)> {
+        self.when
+            .iter()
+            .flat_map(|when| when.captures(req))
+            .collect()
+    }
+
+    /// The response to answer the request with, for destinations that don't reroute it.
+    pub fn response(&self, req: &HttpRequest) -> Option<HttpResponse> {
+        if !self.should_redirect_request(req) {
+            return None;
+        }
+
+        match &self.to {
+            RedirectDestination::Location(dest) => {
+                let location = expand_captures(&dest.url, &self.captures(req));
+                Some(HttpResponse {
+                    headers: vec![
+                        (http::header::LOCATION.to_string(), location),
+                        (http::header::CONTENT_LENGTH.to_string(), "0".into()),
+                    ],
+                    status: dest.status,
+                    body: vec![],
+                    request_path: req.path.clone(),
+                    request_authority: req.authority.clone(),
+                    request_host: req.host.clone(),
+                    request_scheme: req.scheme.clone(),
+                    request_version: req.version.clone(),
+                    request_headers: req.headers.clone(),
+                    request_method: req.method.clone(),
+                })
+            }
+            RedirectDestination::File(dest) => Some(
+                dest.resp(req)
+                    .unwrap_or_else(|err| dest.response(req, 500, vec![], err.to_string().into())),
+            ),
+            RedirectDestination::Url(_) => None,
+        }
+    }
+
     pub fn redirect_request(&self, req: &mut HttpRequest) {
         if !self.should_redirect_request(req) {
             return;
         }
 
         match &self.to {
-            RedirectDestination::File(_) => {}
+            RedirectDestination::File(_) | RedirectDestination::Location(_) => {}
             RedirectDestination::Url(dest) => {
                 let (scheme, authority, host) =
                     match (dest.url.scheme(), dest.url.authority(), dest.url.host()) {
@@ -192,6 +470,24 @@ fn default_when() -> Vec<RuleMatch> {
     vec![]
 }
 
+/// Accepts a single redirect as well as a list of them.
+pub fn deserialize_redirects<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<RequestRedirect>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Redirects {
+        One(Box<RequestRedirect>),
+        Many(Vec<RequestRedirect>),
+    }
+
+    Ok(match Option::<Redirects>::deserialize(deserializer)? {
+        Some(Redirects::One(redirect)) => vec![*redirect],
+        Some(Redirects::Many(redirects)) => redirects,
+        None => vec![],
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -254,6 +550,15 @@ mod test {
         "/usr/local/www/file.json"
         ; "rewrite without replacing"
     )]
+    #[test_case(
+        true,
+        false,
+        "/usr/local/www",
+        None,
+        "https://google.com/search/a%20b.json?page=2",
+        "/usr/local/www/search/a b.json"
+        ; "rewrite without the query"
+    )]
     fn tests_file_redirect_calculate_path(
         replace_path: bool,
         root_index: bool,
@@ -267,7 +572,7 @@ mod test {
             root_index,
             path: PathBuf::from(file_path),
             file_suffix,
-            content_type: "application/json".into(),
+            content_type: Some("application/json".into()),
         };
         let uri: Uri = req_path.parse().expect("should parse the req path");
         let req = HttpRequest {
@@ -286,7 +591,9 @@ mod test {
             method: "GET".into(),
             body: vec![],
         };
-        let new_path = dest.path_for_request(&req);
+        let new_path = dest
+            .path_for_request(&req)
+            .expect("should stay within the path");
 
         assert_eq!(new_path.as_os_str(), std::ffi::OsStr::new(expected_path));
     }
@@ -303,7 +610,7 @@ mod test {
             replace_path: true,
             root_index: true,
             file_suffix: Some(".html".into()),
-            content_type: "text/html; charset=UTF-8".into(),
+            content_type: Some("text/html; charset=UTF-8".into()),
         };
         let req = HttpRequest {
             path: "/".into(),
@@ -335,4 +642,193 @@ mod test {
         assert_eq!(content_type_header.1, "text/html; charset=UTF-8");
         assert_eq!(content_length_header.1, resp.body.len().to_string());
     }
+
+    #[test]
+    fn redirects_to_location() {
+        let redirect: RequestRedirect = serde_yaml::from_str(
+            r#"
+            when:
+              - path:
+                  regex: ^/old/(?P<rest>.*)$
+              - header:
+                  header_name:
+                    exact: accept-language
+                  header_value:
+                    regex: ^(?P<lang>[a-z]{2})
+            to:
+              location:
+                url: https://new.foo.com/${lang}/$rest
+                status: 308
+            "#,
+        )
+        .expect("should parse the redirect");
+        let mut request = HttpRequest {
+            path: "/old/docs/index.html?q=1".into(),
+            authority: "foo.com".into(),
+            host: "foo.com".into(),
+            scheme: "https".into(),
+            version: "HTTP/1.1".into(),
+            headers: vec![("accept-language".into(), "de-DE".into())],
+            method: "GET".into(),
+            body: vec![],
+        };
+
+        let resp = redirect
+            .response(&request)
+            .expect("should respond with a redirect");
+        assert_eq!(resp.status, 308);
+        assert_eq!(
+            resp.headers[0],
+            (
+                "location".into(),
+                "https://new.foo.com/de/docs/index.html".into()
+            )
+        );
+
+        // A redirect the client follows leaves the request alone
+        redirect.redirect_request(&mut request);
+        assert_eq!(request.host, "foo.com");
+        request.path = "/new/docs".into();
+        assert!(redirect.response(&request).is_none());
+    }
+
+    #[test]
+    fn rejects_non_redirect_statuses() {
+        let redirect: Result<RequestRedirect, _> = serde_yaml::from_str(
+            r#"
+            to:
+              location:
+                url: https://foo.com
+                status: 200
+            "#,
+        );
+        assert!(redirect.is_err());
+    }
+
+    #[test_case("/../secret" ; "parent directory")]
+    #[test_case("/files/%2e%2e/%2E%2E/secret" ; "encoded parent directory")]
+    #[test_case("/files/..%2fsecret" ; "encoded separator")]
+    fn prevents_path_traversal(path: &str) {
+        let dest = FileDestination {
+            path: PathBuf::from("/usr/local/www"),
+            root_index: false,
+            replace_path: true,
+            file_suffix: None,
+            content_type: None,
+        };
+        let req = HttpRequest {
+            path: path.into(),
+            authority: "foo.com".into(),
+            host: "foo.com".into(),
+            scheme: "https".into(),
+            version: "HTTP/1.1".into(),
+            headers: vec![],
+            method: "GET".into(),
+            body: vec![],
+        };
+        assert_eq!(dest.path_for_request(&req), None);
+        let resp = dest.resp(&req).expect("should respond");
+        assert_eq!(resp.status, 404);
+    }
+
+    #[test_case(None, ByteRange::Full ; "no range")]
+    #[test_case(Some("bytes=0-3"), ByteRange::Partial(0, 3) ; "start and end")]
+    #[test_case(Some("bytes=6-"), ByteRange::Partial(6, 9) ; "open ended")]
+    #[test_case(Some("bytes=-4"), ByteRange::Partial(6, 9) ; "suffix")]
+    #[test_case(Some("bytes=8-20"), ByteRange::Partial(8, 9) ; "past the end")]
+    #[test_case(Some("bytes=10-"), ByteRange::Unsatisfiable ; "unsatisfiable")]
+    #[test_case(Some("bytes=0-1,4-5"), ByteRange::Full ; "multiple ranges")]
+    #[test_case(Some("items=0-1"), ByteRange::Full ; "other units")]
+    fn parses_byte_ranges(range: Option<&str>, expected: ByteRange) {
+        assert_eq!(byte_range(range, 10), expected);
+    }
+
+    fn header<'a>(resp: &'a HttpResponse, name: &http::header::HeaderName) -> Option<&'a str> {
+        resp.headers
+            .iter()
+            .find(|(header, _value)| header == name.as_str())
+            .map(|(_header, value)| value.as_str())
+    }
+
+    #[test]
+    fn serves_files() {
+        let dir = TempDir::new("map_local").expect("should build a temporary directory");
+        std::fs::create_dir(dir.path().join("docs")).expect("should create the directory");
+        std::fs::write(dir.path().join("docs").join("index.html"), "<h1>docs</h1>")
+            .expect("should write file");
+        std::fs::write(dir.path().join("data.json"), r#"{"id": 1234}"#).expect("should write file");
+        std::fs::write(dir.path().join("index.html"), "<h1>home</h1>").expect("should write file");
+
+        let redirect = RequestRedirect {
+            when: vec![],
+            to: RedirectDestination::File(FileDestination {
+                path: dir.path().to_path_buf(),
+                root_index: true,
+                replace_path: true,
+                file_suffix: None,
+                content_type: None,
+            }),
+        };
+        let request = |path: &str, headers: &[(&str, &str)]| HttpRequest {
+            path: path.into(),
+            authority: "foo.com".into(),
+            host: "foo.com".into(),
+            scheme: "https".into(),
+            version: "HTTP/1.1".into(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            method: "GET".into(),
+            body: vec![],
+        };
+        let respond = |req: HttpRequest| redirect.response(&req).expect("should respond");
+
+        let resp = respond(request("/data.json", &[]));
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, br#"{"id": 1234}"#);
+        assert_eq!(
+            header(&resp, &http::header::CONTENT_TYPE),
+            Some("application/json")
+        );
+        assert!(header(&resp, &http::header::LAST_MODIFIED).is_some());
+        let etag = header(&resp, &http::header::ETAG)
+            .expect("should have an etag")
+            .to_string();
+
+        let resp = respond(request("/data.json", &[("If-None-Match", &etag)]));
+        assert_eq!(resp.status, 304);
+        assert!(resp.body.is_empty());
+
+        let resp = respond(request("/data.json", &[("Range", "bytes=7-10")]));
+        assert_eq!(resp.status, 206);
+        assert_eq!(resp.body, b"1234");
+        assert_eq!(
+            header(&resp, &http::header::CONTENT_RANGE),
+            Some("bytes 7-10/12")
+        );
+        let resp = respond(request(
+            "/data.json",
+            &[("Range", "bytes=7-10"), ("If-Range", "\"stale\"")],
+        ));
+        assert_eq!(resp.status, 200);
+
+        let resp = respond(request("/docs", &[]));
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, b"<h1>docs</h1>");
+        assert_eq!(
+            header(&resp, &http::header::CONTENT_TYPE),
+            Some("text/html; charset=UTF-8")
+        );
+
+        // Without a file suffix, trailing slashes serve the directory's index.html too
+        let resp = respond(request("/docs/", &[]));
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, b"<h1>docs</h1>");
+        let resp = respond(request("/?page=2", &[]));
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, b"<h1>home</h1>");
+
+        assert_eq!(respond(request("/missing.json", &[])).status, 404);
+    }
 }
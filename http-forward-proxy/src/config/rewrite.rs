@@ -1,9 +1,9 @@
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr};
 
-use crate::config::{deserialize_regex, serialize_regex};
+use crate::config::{deserialize_regex, intercept::request_port, serialize_regex};
 use http::header::{HeaderName, HeaderValue};
 use proxysaur_bindings::http::{request::HttpRequestResult as HttpRequest, response::HttpResponse};
-use regex::Regex;
+use regex::{Captures, Regex};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// Represents a String or a regular expression
@@ -43,6 +43,9 @@ impl MatchValue {
         }
     }
 
+    /// Expands `replace_with` when `value` matches. `$0` is the whole value, and `$1` the text
+    /// a `contains` found, with any other `$` left as it is. A regex expands its groups like
+    /// [`expand_captures`], where `$0` is its first match.
     pub fn expand(&self, value: &str, replace_with: &str) -> String {
         match self {
             MatchValue::Exact(s) if s == value => replace_with.replace("$0", s),
@@ -50,24 +53,67 @@ impl MatchValue {
                 let replaced = replace_with.replace("$0", value);
                 replaced.replace("$1", s)
             }
-            MatchValue::Regex(regex) if regex.is_match(value) => {
-                let mut into = String::new();
-                if let Some(captures) = regex.captures(value) {
-                    if captures.len() == 1 {
-                        regex.replace_all(value, replace_with).into()
-                    } else {
-                        captures.expand(replace_with, &mut into);
-                        into
-                    }
-                } else {
-                    "".into()
+            MatchValue::Regex(regex) => match regex.captures(value) {
+                // Without groups, every match is replaced instead
+                Some(captures) if captures.len() == 1 => self.replace_all(value, replace_with),
+                Some(captures) => expand_captures(replace_with, &regex_groups(regex, &captures)),
+                None => "".into(),
+            },
+            _ => "".into(),
+        }
+    }
+
+    /// The capture groups when `value` matches, by name and by number, for [`expand_captures`].
+    /// `0` is the whole value, even when a regex only matches part of it.
+    pub fn captures(&self, value: &str) -> Vec<(String, String)> {
+        match self {
+            MatchValue::Regex(regex) => match regex.captures(value) {
+                Some(captures) => {
+                    let mut groups = regex_groups(regex, &captures);
+                    groups[0].1 = value.to_string();
+                    groups
                 }
+                None => vec![],
+            },
+            value_match if value_match.matches(value) => {
+                vec![("0".to_string(), value.to_string())]
             }
-            _ => "".into(),
+            _ => vec![],
+        }
+    }
+
+    /// Replaces every match in `value`, expanding `replace_with` like [`expand_captures`]. `$0`
+    /// is the matched text, and a regex can also refer to its capture groups.
+    pub fn replace_all(&self, value: &str, replace_with: &str) -> String {
+        match self {
+            MatchValue::Exact(s) | MatchValue::Contains(s) if !s.is_empty() => {
+                let replace_with = expand_captures(replace_with, &[("0".into(), s.clone())]);
+                value.replace(s.as_str(), &replace_with)
+            }
+            MatchValue::Exact(_) | MatchValue::Contains(_) => value.into(),
+            MatchValue::Regex(regex) => regex
+                .replace_all(value, |captures: &Captures| {
+                    expand_captures(replace_with, &regex_groups(regex, captures))
+                })
+                .into(),
         }
     }
 }
 
+/// The groups of a regex match by number, from `0` for the whole match, and by name.
+fn regex_groups(regex: &Regex, captures: &Captures) -> Vec<(String, String)> {
+    let mut groups = vec![];
+    for (idx, name) in regex.capture_names().enumerate() {
+        if let Some(group) = captures.get(idx) {
+            groups.push((idx.to_string(), group.as_str().to_string()));
+            if let Some(name) = name {
+                groups.push((name.to_string(), group.as_str().to_string()));
+            }
+        }
+    }
+    groups
+}
+
 #[cfg(test)]
 mod test_match_value {
     use super::*;
@@ -119,6 +165,21 @@ mod test_match_value {
         let expanded = value.expand("/api/v2/resource/v3/book", template);
         assert_eq!("/api/v8/resource/v8/book", expanded);
     }
+
+    #[test]
+    fn replace_all() {
+        let regex = Regex::new(r#""id": (?P<id>\d+)"#).expect("should compile the regex");
+        let value = MatchValue::Regex(regex);
+        let replaced = value.replace_all(r#"[{"id": 1}, {"id": 2}]"#, r#""id": "$id""#);
+        assert_eq!(r#"[{"id": "1"}, {"id": "2"}]"#, replaced);
+        // Unknown groups expand to nothing, as in redirect locations
+        let replaced = value.replace_all(r#"{"id": 1}"#, r#""id": "${id}$missing$$""#);
+        assert_eq!(r#"{"id": "1$"}"#, replaced);
+
+        let value = MatchValue::Contains("cat".into());
+        assert_eq!("dog, dog", value.replace_all("cat, cat", "dog"));
+        assert_eq!("[cat]", value.replace_all("cat", "[$0]"));
+    }
 }
 
 /// Where we specify the rewrite
@@ -128,6 +189,12 @@ pub enum Rewrite {
     Header(HeaderRewrite),
     Body(BodyRewrite),
     Status(StatusRewrite),
+    Headers(HeadersRewrite),
+    FindReplace(FindReplaceRewrite),
+    MergePatch(MergePatchRewrite),
+    JsonPatch(JsonPatchRewrite),
+    JsonPath(JsonPathRewrite),
+    BodyFile(BodyFileRewrite),
 }
 
 fn add_content_length(length: usize, headers: &mut Vec<(String, String)>) {
@@ -156,24 +223,82 @@ fn remove_content_encoding(headers: &mut Vec<(String, String)>) {
     }
 }
 
+/// The body as it was sent, for rewrites that edit it rather than replace it. Encoded bodies
+/// would have to be decoded first, so they aren't edited.
+fn editable_body<'a>(body: &'a [u8], headers: &[(String, String)]) -> Result<&'a [u8], String> {
+    let encoding = headers
+        .iter()
+        .find(|(h, _v)| h.eq_ignore_ascii_case(http::header::CONTENT_ENCODING.as_str()))
+        .map(|(_h, v)| v.trim())
+        .filter(|v| !v.eq_ignore_ascii_case("identity"));
+    match encoding {
+        Some(encoding) => Err(format!("body is {encoding} encoded")),
+        None => Ok(body),
+    }
+}
+
+/// Parses `body` as JSON, applies `rewrite` and serializes the result.
+fn rewrite_json(
+    body: &[u8],
+    headers: &[(String, String)],
+    rewrite: impl FnOnce(&mut serde_json::Value) -> Result<(), String>,
+) -> Result<Vec<u8>, String> {
+    let mut json: serde_json::Value = serde_json::from_slice(editable_body(body, headers)?)
+        .map_err(|err| format!("body is not JSON: {err}"))?;
+    rewrite(&mut json)?;
+    serde_json::to_vec(&json).map_err(|err| err.to_string())
+}
+
 impl Rewrite {
-    pub fn rewrite_req(&self, mut req: HttpRequest) -> HttpRequest {
+    /// The rewritten body, if this rewrites bodies. `headers` are those sent with `body`.
+    pub fn rewrite_body(
+        &self,
+        body: &[u8],
+        headers: &[(String, String)],
+    ) -> Option<Result<Vec<u8>, String>> {
+        let body = match self {
+            Rewrite::Header(_) | Rewrite::Headers(_) | Rewrite::Status(_) => return None,
+            Rewrite::Body(rewrite) => Ok(rewrite.replace_with.clone()),
+            Rewrite::FindReplace(rewrite) => editable_body(body, headers).and_then(|body| {
+                let body =
+                    std::str::from_utf8(body).map_err(|err| format!("body is not UTF-8: {err}"))?;
+                Ok(rewrite
+                    .find
+                    .replace_all(body, &rewrite.replace)
+                    .into_bytes())
+            }),
+            Rewrite::MergePatch(rewrite) => rewrite_json(body, headers, |json| {
+                json_patch::merge(json, &rewrite.merge_patch);
+                Ok(())
+            }),
+            Rewrite::JsonPatch(rewrite) => rewrite_json(body, headers, |json| {
+                json_patch::patch(json, &rewrite.json_patch.0).map_err(|err| err.to_string())
+            }),
+            Rewrite::JsonPath(rewrite) => rewrite_json(body, headers, |json| rewrite.apply(json)),
+            Rewrite::BodyFile(rewrite) => std::fs::read(&rewrite.replace_with_file)
+                .map_err(|err| format!("{}: {err}", rewrite.replace_with_file.display())),
+        };
+        Some(body)
+    }
+
+    pub fn rewrite_req(&self, req: &mut HttpRequest) -> Result<(), String> {
         match self {
-            Rewrite::Body(rewrite) => {
-                let replace = rewrite.replace_with.clone();
-                req.body = replace;
-                add_content_length(req.body.len(), &mut req.headers);
-                req
-            }
             Rewrite::Header(rewrite) => {
                 rewrite.do_rewrite(&mut req.headers);
-                req
             }
-            Rewrite::Status(_) => req,
+            Rewrite::Headers(rewrite) => rewrite.apply(&mut req.headers)?,
+            Rewrite::Status(_) => {}
+            rewrite => {
+                if let Some(body) = rewrite.rewrite_body(&req.body, &req.headers) {
+                    req.body = body?;
+                    add_content_length(req.body.len(), &mut req.headers);
+                }
+            }
         }
+        Ok(())
     }
 
-    pub fn rewrite_resp(&self, resp: &mut HttpResponse) {
+    pub fn rewrite_resp(&self, resp: &mut HttpResponse) -> Result<(), String> {
         match self {
             Rewrite::Status(rewrite) => {
                 let status = resp.status.to_string();
@@ -187,16 +312,19 @@ impl Rewrite {
                     }
                 }
             }
-            Rewrite::Body(rewrite) => {
-                let replace = rewrite.replace_with.clone();
-                resp.body = replace;
-                add_content_length(resp.body.len(), &mut resp.headers);
-                remove_content_encoding(&mut resp.headers);
-            }
             Rewrite::Header(rewrite) => {
                 rewrite.do_rewrite(&mut resp.headers);
             }
+            Rewrite::Headers(rewrite) => rewrite.apply(&mut resp.headers)?,
+            rewrite => {
+                if let Some(body) = rewrite.rewrite_body(&resp.body, &resp.headers) {
+                    resp.body = body?;
+                    add_content_length(resp.body.len(), &mut resp.headers);
+                    remove_content_encoding(&mut resp.headers);
+                }
+            }
         }
+        Ok(())
     }
 }
 
@@ -215,6 +343,60 @@ pub struct BodyRewrite {
     pub(crate) replace_with: Vec<u8>,
 }
 
+/// Replaces every match of `find` in a text body, expanding `replace` like
+/// [`MatchValue::replace_all`]. Fails on bodies that aren't UTF-8 or are encoded.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FindReplaceRewrite {
+    pub(crate) find: MatchValue,
+    pub(crate) replace: String,
+}
+
+/// Applies an RFC 7396 JSON merge patch to a JSON body.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MergePatchRewrite {
+    pub(crate) merge_patch: serde_json::Value,
+}
+
+/// Applies RFC 6902 JSON Patch operations to a JSON body.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct JsonPatchRewrite {
+    pub(crate) json_patch: json_patch::Patch,
+}
+
+impl Eq for JsonPatchRewrite {}
+
+/// Replaces with `set`, or removes, the values a JSONPath selects from a JSON body.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JsonPathRewrite {
+    pub(crate) json_path: String,
+    #[serde(default)]
+    pub(crate) set: Option<serde_json::Value>,
+    #[serde(default)]
+    pub(crate) remove: bool,
+}
+
+impl JsonPathRewrite {
+    fn apply(&self, json: &mut serde_json::Value) -> Result<(), String> {
+        let value = json.take();
+        // Returning `None` from the replacement removes the value, where `delete` nulls it
+        let rewritten = match (&self.set, self.remove) {
+            (_, true) => jsonpath_lib::replace_with(value, &self.json_path, &mut |_| None),
+            (Some(set), false) => {
+                jsonpath_lib::replace_with(value, &self.json_path, &mut |_| Some(set.clone()))
+            }
+            (None, false) => return Err("a JSONPath rewrite needs `set` or `remove`".into()),
+        };
+        *json = rewritten.map_err(|err| format!("{err:?}"))?;
+        Ok(())
+    }
+}
+
+/// Replaces the body with the contents of a file, read on each rewrite.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BodyFileRewrite {
+    pub(crate) replace_with_file: PathBuf,
+}
+
 fn serialize_replace<S: Serializer>(replace_with: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
     let val =
         std::str::from_utf8(replace_with).map_err(|_| serde::ser::Error::custom("UTF-8 error"))?;
@@ -269,40 +451,575 @@ impl HeaderRewrite {
     }
 }
 
+/// A header name and value to add, set or append.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HeaderEntry {
+    pub(crate) name: String,
+    pub(crate) value: String,
+}
+
+impl HeaderEntry {
+    fn header(&self) -> Result<(String, String), String> {
+        HeaderName::from_str(&self.name)
+            .map_err(|_| format!("invalid header name: {}", self.name))?;
+        HeaderValue::from_str(&self.value)
+            .map_err(|_| format!("invalid value for header {}: {}", self.name, self.value))?;
+        Ok((self.name.clone(), self.value.clone()))
+    }
+}
+
+/// Headers to remove by name, and only those with a matching value when given.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HeaderRemoval {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) value: Option<MatchValue>,
+}
+
+/// An operation on the headers of a request or response. Names are matched case-insensitively.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HeaderOperation {
+    /// Adds the header when there isn't one with the same name
+    #[serde(rename = "add")]
+    Add(HeaderEntry),
+    /// Replaces every header with the same name, or adds the header
+    #[serde(rename = "set")]
+    Set(HeaderEntry),
+    /// Adds the header alongside any others with the same name, such as another `Set-Cookie`
+    #[serde(rename = "append")]
+    Append(HeaderEntry),
+    /// Removes every header with the name
+    #[serde(rename = "remove")]
+    Remove(HeaderRemoval),
+}
+
+/// Header operations, applied in order.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HeadersRewrite {
+    pub(crate) headers: Vec<HeaderOperation>,
+}
+
+impl HeadersRewrite {
+    pub fn apply(&self, headers: &mut Vec<(String, String)>) -> Result<(), String> {
+        for operation in self.headers.iter() {
+            match operation {
+                HeaderOperation::Add(entry) => {
+                    let header = entry.header()?;
+                    if !headers
+                        .iter()
+                        .any(|(name, _value)| name.eq_ignore_ascii_case(&entry.name))
+                    {
+                        headers.push(header);
+                    }
+                }
+                HeaderOperation::Set(entry) => {
+                    let header = entry.header()?;
+                    // Keeps the header where the first one with the name was
+                    let position = headers
+                        .iter()
+                        .position(|(name, _value)| name.eq_ignore_ascii_case(&entry.name));
+                    headers.retain(|(name, _value)| !name.eq_ignore_ascii_case(&entry.name));
+                    match position {
+                        Some(idx) => headers.insert(idx, header),
+                        None => headers.push(header),
+                    }
+                }
+                HeaderOperation::Append(entry) => headers.push(entry.header()?),
+                HeaderOperation::Remove(HeaderRemoval { name, value }) => {
+                    headers.retain(|(header_name, header_value)| {
+                        !header_name.eq_ignore_ascii_case(name)
+                            || value
+                                .as_ref()
+                                .map_or(false, |value| !value.matches(header_value))
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Request paths include the query string, which path matchers leave out.
+pub(crate) fn path_without_query(path: &str) -> &str {
+    path.split('?').next().unwrap_or_default()
+}
+
+/// Matches a query parameter by name, and by value when given.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QueryMatch {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) value: Option<MatchValue>,
+}
+
+/// Matches the values a JSONPath selects from a JSON body. Without a `value`, matches when the
+/// path selects anything. Strings are matched as they are, other values as JSON.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JsonPathMatch {
+    pub(crate) path: String,
+    #[serde(default)]
+    pub(crate) value: Option<MatchValue>,
+}
+
+impl JsonPathMatch {
+    pub fn matches(&self, body: &[u8]) -> bool {
+        let json: serde_json::Value = match serde_json::from_slice(body) {
+            Ok(json) => json,
+            Err(_) => return false,
+        };
+        let selected = match jsonpath_lib::select(&json, &self.path) {
+            Ok(selected) => selected,
+            Err(_) => return false,
+        };
+        match &self.value {
+            None => !selected.is_empty(),
+            Some(value) => selected.iter().any(|selected| match selected {
+                serde_json::Value::String(selected) => value.matches(selected),
+                selected => value.matches(&selected.to_string()),
+            }),
+        }
+    }
+}
+
+/// Decodes `%XX` escapes, leaving invalid ones as they are.
+pub(crate) fn percent_decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let hex = bytes
+            .get(idx + 1..idx + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[idx], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn decode_query_component(component: &str) -> String {
+    percent_decode(&component.replace('+', " "))
+}
+
+/// The decoded parameters of the query string of `path`, in order.
+fn query_params(path: &str) -> Vec<(String, String)> {
+    let query = match path.split_once('?') {
+        Some((_path, query)) => query,
+        None => return vec![],
+    };
+    query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.split_once('=') {
+            Some((name, value)) => (decode_query_component(name), decode_query_component(value)),
+            None => (decode_query_component(param), String::new()),
+        })
+        .collect()
+}
+
+/// Matches a status code or an inclusive range of them. Written as a code, `500`, a range,
+/// `"500-599"`, or a class, `"5xx"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatusMatch {
+    pub(crate) min: u16,
+    pub(crate) max: u16,
+}
+
+impl StatusMatch {
+    pub fn matches(&self, status: u16) -> bool {
+        (self.min..=self.max).contains(&status)
+    }
+}
+
+impl FromStr for StatusMatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || format!("invalid status match: {s}");
+        if let Some(class) = s.strip_suffix("xx").or_else(|| s.strip_suffix("XX")) {
+            let class: u16 = class.parse().map_err(|_| invalid())?;
+            if !(1..=9).contains(&class) {
+                return Err(invalid());
+            }
+            return Ok(Self {
+                min: class * 100,
+                max: class * 100 + 99,
+            });
+        }
+        let (min, max) = match s.split_once('-') {
+            Some((min, max)) => (min.trim(), max.trim()),
+            None => (s, s),
+        };
+        let min: u16 = min.parse().map_err(|_| invalid())?;
+        let max: u16 = max.parse().map_err(|_| invalid())?;
+        if min > max {
+            return Err(invalid());
+        }
+        Ok(Self { min, max })
+    }
+}
+
+impl Serialize for StatusMatch {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.min == self.max {
+            serializer.serialize_u16(self.min)
+        } else {
+            serializer.serialize_str(&format!("{}-{}", self.min, self.max))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for StatusMatch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum StatusValue {
+            Code(u16),
+            Text(String),
+        }
+
+        match StatusValue::deserialize(deserializer)? {
+            StatusValue::Code(status) => Ok(Self {
+                min: status,
+                max: status,
+            }),
+            StatusValue::Text(status) => status.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl HeaderMatch {
+    pub fn matches(&self, headers: &[(String, String)]) -> bool {
+        let matched_header = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                if self.header_name.matches(name.as_str()) {
+                    Some(value.as_str())
+                } else {
+                    None
+                }
+            })
+            .next();
+        if let Some(value) = matched_header {
+            self.header_value.matches(value)
+        } else {
+            false
+        }
+    }
+}
+
 /// Matches on either plaintext or a regular expression
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum RuleMatch {
+    /// The path of the request, without the query string
     #[serde(rename = "path")]
     PathMatch(MatchValue),
     #[serde(rename = "header")]
     HeaderMatch(HeaderMatch),
+    #[serde(rename = "method")]
+    MethodMatch(MatchValue),
+    #[serde(rename = "query")]
+    QueryMatch(QueryMatch),
+    #[serde(rename = "scheme")]
+    SchemeMatch(MatchValue),
+    /// The port the request is sent to, defaulting to the scheme's
+    #[serde(rename = "port")]
+    PortMatch(u16),
+    /// The request body, as text
+    #[serde(rename = "body")]
+    BodyMatch(MatchValue),
+    #[serde(rename = "json_path")]
+    JsonPathMatch(JsonPathMatch),
+    /// The response status. Only matches responses.
+    #[serde(rename = "status")]
+    StatusMatch(StatusMatch),
+    /// A response header. Only matches responses.
+    #[serde(rename = "response_header")]
+    ResponseHeaderMatch(HeaderMatch),
+    /// The response body, as text. Only matches responses.
+    #[serde(rename = "response_body")]
+    ResponseBodyMatch(MatchValue),
+    /// Only matches responses.
+    #[serde(rename = "response_json_path")]
+    ResponseJsonPathMatch(JsonPathMatch),
+    /// Matches when any of the matchers match
+    #[serde(rename = "any")]
+    Any(Vec<RuleMatch>),
+    /// Matches when all of the matchers match
+    #[serde(rename = "all")]
+    All(Vec<RuleMatch>),
+    #[serde(rename = "not")]
+    Not(Box<RuleMatch>),
 }
 
 impl RuleMatch {
+    /// Whether the request matches. Matchers with a response condition anywhere in them, even
+    /// under `not`, never match a request.
     pub fn matches(&self, req: &HttpRequest) -> bool {
+        !self.needs_response() && self.evaluate(req, None)
+    }
+
+    /// Whether the response, or the request it answers, matches.
+    pub fn matches_response(&self, req: &HttpRequest, resp: &HttpResponse) -> bool {
+        self.evaluate(req, Some(resp))
+    }
+
+    /// Whether any of the conditions are on the response.
+    fn needs_response(&self) -> bool {
         match self {
-            RuleMatch::PathMatch(path) => path.matches(&req.path),
-            RuleMatch::HeaderMatch(HeaderMatch {
-                header_name,
-                header_value,
-            }) => {
-                let matched_header = req
-                    .headers
-                    .iter()
-                    .filter_map(|(name, value)| {
-                        if header_name.matches(name.as_str()) {
-                            Some(value.as_str())
-                        } else {
-                            None
-                        }
-                    })
-                    .next();
-                if let Some(value) = matched_header {
-                    header_value.matches(value)
-                } else {
-                    false
-                }
+            RuleMatch::StatusMatch(_)
+            | RuleMatch::ResponseHeaderMatch(_)
+            | RuleMatch::ResponseBodyMatch(_)
+            | RuleMatch::ResponseJsonPathMatch(_) => true,
+            RuleMatch::Any(matchers) | RuleMatch::All(matchers) => {
+                matchers.iter().any(RuleMatch::needs_response)
+            }
+            RuleMatch::Not(matcher) => matcher.needs_response(),
+            _ => false,
+        }
+    }
+
+    fn evaluate(&self, req: &HttpRequest, resp: Option<&HttpResponse>) -> bool {
+        match self {
+            RuleMatch::PathMatch(path) => path.matches(path_without_query(&req.path)),
+            RuleMatch::HeaderMatch(header_match) => header_match.matches(&req.headers),
+            RuleMatch::MethodMatch(method) => method.matches(&req.method),
+            RuleMatch::QueryMatch(QueryMatch { name, value }) => query_params(&req.path)
+                .iter()
+                .filter(|(param, _param_value)| param == name)
+                .any(|(_param, param_value)| {
+                    value
+                        .as_ref()
+                        .map_or(true, |value| value.matches(param_value))
+                }),
+            RuleMatch::SchemeMatch(scheme) => scheme.matches(&req.scheme),
+            RuleMatch::PortMatch(port) => request_port(&req.authority, &req.scheme) == Some(*port),
+            RuleMatch::BodyMatch(body) => body.matches(&String::from_utf8_lossy(&req.body)),
+            RuleMatch::JsonPathMatch(json_path) => json_path.matches(&req.body),
+            RuleMatch::StatusMatch(status) => {
+                resp.map_or(false, |resp| status.matches(resp.status))
+            }
+            RuleMatch::ResponseHeaderMatch(header_match) => {
+                resp.map_or(false, |resp| header_match.matches(&resp.headers))
+            }
+            RuleMatch::ResponseBodyMatch(body) => resp.map_or(false, |resp| {
+                body.matches(&String::from_utf8_lossy(&resp.body))
+            }),
+            RuleMatch::ResponseJsonPathMatch(json_path) => {
+                resp.map_or(false, |resp| json_path.matches(&resp.body))
             }
+            RuleMatch::Any(matchers) => matchers.iter().any(|matcher| matcher.evaluate(req, resp)),
+            RuleMatch::All(matchers) => matchers.iter().all(|matcher| matcher.evaluate(req, resp)),
+            RuleMatch::Not(matcher) => !matcher.evaluate(req, resp),
+        }
+    }
+
+    /// The capture groups of the request values this matches, by name and by number. Only
+    /// matchers on text with a value to capture from, and their combinations, capture anything.
+    pub fn captures(&self, req: &HttpRequest) -> Vec<(String, String)> {
+        match self {
+            RuleMatch::PathMatch(path) => path.captures(path_without_query(&req.path)),
+            RuleMatch::HeaderMatch(header_match) => req
+                .headers
+                .iter()
+                .find(|(name, _value)| header_match.header_name.matches(name))
+                .map(|(_name, value)| header_match.header_value.captures(value))
+                .unwrap_or_default(),
+            RuleMatch::MethodMatch(method) => method.captures(&req.method),
+            RuleMatch::QueryMatch(QueryMatch {
+                name,
+                value: Some(value),
+            }) => query_params(&req.path)
+                .iter()
+                .find(|(param, param_value)| param == name && value.matches(param_value))
+                .map(|(_param, param_value)| value.captures(param_value))
+                .unwrap_or_default(),
+            RuleMatch::SchemeMatch(scheme) => scheme.captures(&req.scheme),
+            RuleMatch::Any(matchers) => matchers
+                .iter()
+                .find(|matcher| matcher.matches(req))
+                .map(|matcher| matcher.captures(req))
+                .unwrap_or_default(),
+            RuleMatch::All(matchers) => matchers
+                .iter()
+                .flat_map(|matcher| matcher.captures(req))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Expands `$name`, `${name}` and `$1` in `template` with `captures`, where later captures win
+/// over earlier ones with the same name, and `$$` with `$`. Unknown captures expand to nothing.
+pub fn expand_captures(template: &str, captures: &[(String, String)]) -> String {
+    let capture = |name: &str| {
+        captures
+            .iter()
+            .rev()
+            .find(|(capture, _value)| capture == name)
+            .map_or("", |(_capture, value)| value.as_str())
+    };
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(idx) = rest.find('$') {
+        expanded.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+        } else if let Some((name, after)) = rest
+            .strip_prefix('{')
+            .and_then(|braced| braced.split_once('}'))
+        {
+            expanded.push_str(capture(name));
+            rest = after;
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                expanded.push('$');
+            } else {
+                expanded.push_str(capture(&rest[..end]));
+            }
+            rest = &rest[end..];
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+#[cfg(test)]
+mod rule_match_tests {
+    use super::*;
+
+    fn graphql_request(body: &str) -> HttpRequest {
+        HttpRequest {
+            path: "/graphql?team=core&tag=a%20b".into(),
+            authority: "api.foo.com:8443".into(),
+            host: "api.foo.com".into(),
+            scheme: "https".into(),
+            version: "HTTP/1.1".into(),
+            headers: vec![("content-type".into(), "application/json".into())],
+            method: "POST".into(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    const GRAPHQL_RULE: &str = r#"
+    - method:
+        exact: POST
+    - path:
+        regex: ^/graphql
+    - any:
+        - json_path:
+            path: $.operationName
+            value:
+              exact: GetUser
+        - json_path:
+            path: $.operationName
+            value:
+              exact: ListUsers
+    - not:
+        query:
+          name: debug
+    "#;
+
+    #[test]
+    fn matches_graphql_operations() {
+        let when: Vec<RuleMatch> =
+            serde_yaml::from_str(GRAPHQL_RULE).expect("should parse the rule");
+        let matches = |body: &str| {
+            let req = graphql_request(body);
+            when.iter().all(|matcher| matcher.matches(&req))
+        };
+        assert!(matches(r#"{"operationName": "GetUser", "query": "..."}"#));
+        assert!(matches(r#"{"operationName": "ListUsers"}"#));
+        assert!(!matches(r#"{"operationName": "DeleteUser"}"#));
+        assert!(!matches("not json"));
+    }
+
+    #[test]
+    fn matches_request_parts() {
+        let req = graphql_request(r#"{"variables": {"id": 42}}"#);
+        let matchers: Vec<RuleMatch> = serde_yaml::from_str(
+            r#"
+            - query:
+                name: tag
+                value:
+                  exact: a b
+            - query:
+                name: team
+            - scheme:
+                exact: https
+            - port: 8443
+            - body:
+                contains: variables
+            - json_path:
+                path: $.variables.id
+                value:
+                  exact: "42"
+            - all:
+                - method:
+                    regex: ^(POST|PUT)$
+                - not:
+                    port: 443
+            "#,
+        )
+        .expect("should parse the matchers");
+        for matcher in matchers.iter() {
+            assert!(matcher.matches(&req), "{matcher:?} should match");
+        }
+
+        let misses = [
+            RuleMatch::QueryMatch(QueryMatch {
+                name: "missing".into(),
+                value: None,
+            }),
+            RuleMatch::PortMatch(443),
+            RuleMatch::Any(vec![]),
+            RuleMatch::Not(Box::new(RuleMatch::All(vec![]))),
+        ];
+        for matcher in misses.iter() {
+            assert!(!matcher.matches(&req), "{matcher:?} should not match");
+        }
+    }
+
+    #[test]
+    fn response_conditions_never_match_requests() {
+        let req = graphql_request(r#"{"variables": {"id": 42}}"#);
+        let matchers: Vec<RuleMatch> = serde_yaml::from_str(
+            r#"
+            - status: 500
+            - not:
+                status: 5xx
+            - not:
+                all:
+                  - method:
+                      exact: POST
+                  - response_header:
+                      header_name:
+                        exact: content-type
+                      header_value:
+                        contains: json
+            - any:
+                - not:
+                    response_body:
+                      contains: error
+            "#,
+        )
+        .expect("should parse the matchers");
+        for matcher in matchers.iter() {
+            assert!(!matcher.matches(&req), "{matcher:?} should not match");
         }
     }
 }
@@ -328,7 +1045,7 @@ impl RequestRewrite {
             .all(|when: &RuleMatch| when.matches(req))
     }
 
-    pub fn rewrite(&self, req: HttpRequest) -> HttpRequest {
+    pub fn rewrite(&self, req: &mut HttpRequest) -> Result<(), String> {
         self.rewrite.rewrite_req(req)
     }
 }
@@ -337,6 +1054,24 @@ impl RequestRewrite {
 mod request_rewrite_tests {
     use super::*;
 
+    #[test]
+    fn path_match_ignores_query() {
+        let when = RuleMatch::PathMatch(MatchValue::Exact("/".into()));
+        let mut req = HttpRequest {
+            path: "/?page=2".into(),
+            authority: "foo.com".into(),
+            host: "foo.com".into(),
+            scheme: "https".into(),
+            version: "HTTP/1.1".into(),
+            headers: vec![],
+            method: "GET".into(),
+            body: vec![],
+        };
+        assert!(when.matches(&req));
+        req.path = "/search?page=2".into();
+        assert!(!when.matches(&req));
+    }
+
     #[test]
     fn request_header_rewrite() {
         let rewrite = RequestRewrite {
@@ -350,7 +1085,7 @@ mod request_rewrite_tests {
                 new_header_value: "*".into(),
             }),
         };
-        let req = HttpRequest {
+        let mut req = HttpRequest {
             path: "/".into(),
             authority: "foo.com".into(),
             host: "foo.com".into(),
@@ -364,8 +1099,10 @@ mod request_rewrite_tests {
             body: vec![],
         };
         assert!(rewrite.should_rewrite_request(&req));
-        let new_req = rewrite.rewrite(req);
-        let new_value = new_req
+        rewrite
+            .rewrite(&mut req)
+            .expect("should rewrite the request");
+        let new_value = req
             .headers
             .iter()
             .find(|(h, _v)| h == http::header::ACCESS_CONTROL_ALLOW_ORIGIN.as_str())
@@ -373,6 +1110,24 @@ mod request_rewrite_tests {
         assert_eq!(new_value.1, "*");
     }
 
+    #[test]
+    fn header_rewrite_keeps_literal_dollars() {
+        let rewrite = HeaderRewrite {
+            header_match: HeaderMatch {
+                header_name: MatchValue::Exact("x-price".into()),
+                header_value: MatchValue::Contains("".into()),
+            },
+            new_header_name: "$0".into(),
+            new_header_value: "$5.00 from $0".into(),
+        };
+        let mut headers = vec![("x-price".to_string(), "free".to_string())];
+        rewrite.do_rewrite(&mut headers);
+        assert_eq!(
+            headers,
+            vec![("x-price".to_string(), "$5.00 from free".to_string())]
+        );
+    }
+
     #[test]
     fn request_body_rewrite() {
         let rewrite = RequestRewrite {
@@ -381,7 +1136,7 @@ mod request_rewrite_tests {
                 replace_with: "hey!".into(),
             }),
         };
-        let req = HttpRequest {
+        let mut req = HttpRequest {
             path: "/".into(),
             authority: "foo.com".into(),
             host: "foo.com".into(),
@@ -395,8 +1150,10 @@ mod request_rewrite_tests {
             body: vec![],
         };
         assert!(rewrite.should_rewrite_request(&req));
-        let new_req = rewrite.rewrite(req);
-        let content_length_value = new_req
+        rewrite
+            .rewrite(&mut req)
+            .expect("should rewrite the request");
+        let content_length_value = req
             .headers
             .iter()
             .find(|(h, _v)| h == http::header::CONTENT_LENGTH.as_str())
@@ -407,18 +1164,149 @@ mod request_rewrite_tests {
             .expect("should parse to a number");
         assert_eq!(content_length, 4);
     }
+
+    fn json_request(body: &str) -> HttpRequest {
+        HttpRequest {
+            path: "/".into(),
+            authority: "foo.com".into(),
+            host: "foo.com".into(),
+            scheme: "https".into(),
+            version: "HTTP/1.1".into(),
+            headers: vec![("content-length".into(), body.len().to_string())],
+            method: "POST".into(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    fn rewritten(rewrite: &str, body: &str) -> Result<HttpRequest, String> {
+        let rewrite: Rewrite = serde_yaml::from_str(rewrite).expect("should parse the rewrite");
+        let mut req = json_request(body);
+        rewrite.rewrite_req(&mut req)?;
+        let content_length = req.body.len().to_string();
+        assert_eq!(
+            req.headers,
+            vec![("content-length".to_string(), content_length)]
+        );
+        Ok(req)
+    }
+
+    fn rewritten_json(rewrite: &str, body: &str) -> serde_json::Value {
+        let req = rewritten(rewrite, body).expect("should rewrite the request");
+        serde_json::from_slice(&req.body).expect("should be JSON")
+    }
+
+    #[test]
+    fn request_find_replace_rewrite() {
+        let req = rewritten(
+            r#"
+            find:
+              regex: "user-(?P<id>\\d+)"
+            replace: "account-$id"
+            "#,
+            "user-1 and user-22",
+        )
+        .expect("should rewrite the request");
+        assert_eq!(req.body, b"account-1 and account-22");
+
+        let rewrite = Rewrite::FindReplace(FindReplaceRewrite {
+            find: MatchValue::Contains("user".into()),
+            replace: "account".into(),
+        });
+        let mut req = json_request("user-1");
+        req.body = vec![0x1f, 0x8b, 0xff, b'u', b's', b'e', b'r'];
+        assert!(rewrite.rewrite_req(&mut req).is_err());
+        let mut req = json_request("user-1");
+        req.headers.push(("Content-Encoding".into(), "gzip".into()));
+        assert!(rewrite.rewrite_req(&mut req).is_err());
+        assert_eq!(req.body, b"user-1");
+    }
+
+    #[test]
+    fn request_merge_patch_rewrite() {
+        let json = rewritten_json(
+            r#"
+            merge_patch:
+              debug: true
+              user:
+                email: null
+            "#,
+            r#"{"user": {"name": "a", "email": "a@foo.com"}}"#,
+        );
+        assert_eq!(
+            json,
+            serde_json::json!({"debug": true, "user": {"name": "a"}})
+        );
+    }
+
+    #[test]
+    fn request_json_patch_rewrite() {
+        let json = rewritten_json(
+            r#"
+            json_patch:
+              - op: replace
+                path: /items/0
+                value: 10
+              - op: add
+                path: /items/-
+                value: 3
+              - op: remove
+                path: /debug
+            "#,
+            r#"{"items": [1, 2], "debug": true}"#,
+        );
+        assert_eq!(json, serde_json::json!({"items": [10, 2, 3]}));
+
+        let failed = rewritten(
+            r#"
+            json_patch:
+              - op: remove
+                path: /missing
+            "#,
+            "{}",
+        );
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn request_json_path_rewrite() {
+        let body = r#"{"users": [{"name": "a", "token": "1"}, {"name": "b", "token": "2"}]}"#;
+        let json = rewritten_json(
+            r#"
+            json_path: $.users[*].token
+            set: redacted
+            "#,
+            body,
+        );
+        assert_eq!(json["users"][1]["token"], "redacted");
+
+        let json = rewritten_json(
+            r#"
+            json_path: $.users[*].token
+            remove: true
+            "#,
+            body,
+        );
+        assert_eq!(
+            json,
+            serde_json::json!({"users": [{"name": "a"}, {"name": "b"}]})
+        );
+
+        let not_json = rewritten("json_path: $.token\nremove: true", "token=1");
+        assert!(not_json.is_err());
+    }
 }
 
 impl ResponseRewrite {
-    /// Exists because typically the hyper client will consume the request
-    pub fn should_rewrite_response(&self, req: &HttpRequest) -> bool {
+    /// `req` is rebuilt from the response, because typically the hyper client will consume the
+    /// request
+    pub fn should_rewrite_response(&self, req: &HttpRequest, resp: &HttpResponse) -> bool {
         self.when[..]
             .iter()
-            .all(|when: &RuleMatch| when.matches(req))
+            .all(|when: &RuleMatch| when.matches_response(req, resp))
     }
 
-    pub fn rewrite(&self, resp: &mut HttpResponse) {
-        self.rewrite.rewrite_resp(resp);
+    pub fn rewrite(&self, resp: &mut HttpResponse) -> Result<(), String> {
+        self.rewrite.rewrite_resp(resp)
     }
 }
 
@@ -460,8 +1348,10 @@ mod response_rewrite_tests {
             request_headers: vec![],
             request_method: "GET".into(),
         };
-        assert!(rewrite.should_rewrite_response(&req));
-        rewrite.rewrite(&mut resp);
+        assert!(rewrite.should_rewrite_response(&req, &resp));
+        rewrite
+            .rewrite(&mut resp)
+            .expect("should rewrite the response");
         assert_eq!(resp.status, http::StatusCode::OK.as_u16());
     }
 
@@ -498,8 +1388,10 @@ mod response_rewrite_tests {
             request_headers: vec![],
             request_method: "GET".into(),
         };
-        assert!(rewrite.should_rewrite_response(&req));
-        rewrite.rewrite(&mut resp);
+        assert!(rewrite.should_rewrite_response(&req, &resp));
+        rewrite
+            .rewrite(&mut resp)
+            .expect("should rewrite the response");
 
         let content_length_value = resp
             .headers
@@ -553,8 +1445,10 @@ mod response_rewrite_tests {
             request_method: "GET".into(),
         };
 
-        assert!(rewrite.should_rewrite_response(&req));
-        rewrite.rewrite(&mut resp);
+        assert!(rewrite.should_rewrite_response(&req, &resp));
+        rewrite
+            .rewrite(&mut resp)
+            .expect("should rewrite the response");
         let rewritten_header = resp
             .headers
             .iter()
@@ -563,4 +1457,174 @@ mod response_rewrite_tests {
         let rewritten_header_text = &rewritten_header.1;
         assert_eq!(rewritten_header_text, "Basic abcd1234");
     }
+
+    #[test]
+    fn response_conditions() {
+        let rewrite: ResponseRewrite = serde_yaml::from_str(
+            r#"
+            when:
+              - path:
+                  exact: /
+              - status: 5xx
+              - response_header:
+                  header_name:
+                    exact: content-type
+                  header_value:
+                    contains: json
+              - not:
+                  response_json_path:
+                    path: $.retry
+            rewrite:
+              replace_with: "{}"
+            "#,
+        )
+        .expect("should parse the rewrite");
+        let req = HttpRequest {
+            path: "/".into(),
+            authority: "foo.com".into(),
+            host: "foo.com".into(),
+            scheme: "https".into(),
+            version: "HTTP/1.1".into(),
+            headers: vec![],
+            method: "GET".into(),
+            body: vec![],
+        };
+        let resp = |status: u16, content_type: &str, body: &str| HttpResponse {
+            headers: vec![("content-type".into(), content_type.into())],
+            status,
+            body: body.as_bytes().to_vec(),
+            request_path: req.path.clone(),
+            request_authority: req.authority.clone(),
+            request_host: req.host.clone(),
+            request_scheme: req.scheme.clone(),
+            request_version: req.version.clone(),
+            request_headers: vec![],
+            request_method: "GET".into(),
+        };
+
+        assert!(rewrite.should_rewrite_response(&req, &resp(503, "application/json", "{}")));
+        assert!(!rewrite.should_rewrite_response(&req, &resp(200, "application/json", "{}")));
+        assert!(!rewrite.should_rewrite_response(&req, &resp(500, "text/html", "{}")));
+        assert!(!rewrite
+            .should_rewrite_response(&req, &resp(500, "application/json", r#"{"retry": true}"#)));
+        // Response conditions never match requests
+        assert!(!rewrite.when[1].matches(&req));
+    }
+
+    #[test]
+    fn parses_status_matches() {
+        let parse = |status: &str| status.parse::<StatusMatch>();
+        assert_eq!(parse("404"), Ok(StatusMatch { min: 404, max: 404 }));
+        assert_eq!(parse("400-499"), Ok(StatusMatch { min: 400, max: 499 }));
+        assert_eq!(parse("2xx"), Ok(StatusMatch { min: 200, max: 299 }));
+        assert!(parse("500-400").is_err());
+        assert!(parse("xx").is_err());
+        assert!(parse("teapot").is_err());
+    }
+
+    #[test]
+    fn response_body_file_rewrite() {
+        let dir = tempdir::TempDir::new("body_file").expect("should create a temp dir");
+        let path = dir.path().join("body.json");
+        std::fs::write(&path, r#"{"mocked": true}"#).expect("should write the body");
+        let rewrite = Rewrite::BodyFile(BodyFileRewrite {
+            replace_with_file: path,
+        });
+        let mut resp = HttpResponse {
+            headers: vec![("content-encoding".into(), "gzip".into())],
+            status: 200,
+            body: vec![0x1f, 0x8b],
+            request_path: "/".into(),
+            request_authority: "foo.com".into(),
+            request_host: "foo.com".into(),
+            request_scheme: "https".into(),
+            request_version: "HTTP/1.1".into(),
+            request_headers: vec![],
+            request_method: "GET".into(),
+        };
+        rewrite
+            .rewrite_resp(&mut resp)
+            .expect("should rewrite the response");
+        assert_eq!(resp.body, br#"{"mocked": true}"#);
+        assert_eq!(resp.headers, vec![("content-length".into(), "16".into())]);
+
+        let missing = Rewrite::BodyFile(BodyFileRewrite {
+            replace_with_file: dir.path().join("missing.json"),
+        });
+        assert!(missing.rewrite_resp(&mut resp).is_err());
+        assert_eq!(resp.body, br#"{"mocked": true}"#);
+    }
+
+    #[test]
+    fn response_header_operations() {
+        let rewrite: Rewrite = serde_yaml::from_str(
+            r#"
+            headers:
+              - set:
+                  name: Cache-Control
+                  value: no-store
+              - add:
+                  name: x-debug
+                  value: "1"
+              - add:
+                  name: Content-Type
+                  value: text/plain
+              - append:
+                  name: set-cookie
+                  value: debug=1
+              - remove:
+                  name: set-cookie
+                  value:
+                    regex: ^tracking=
+              - remove:
+                  name: X-Powered-By
+            "#,
+        )
+        .expect("should parse the rewrite");
+        let mut resp = HttpResponse {
+            headers: vec![
+                ("content-type".into(), "application/json".into()),
+                ("cache-control".into(), "max-age=60".into()),
+                ("Set-Cookie".into(), "session=abc".into()),
+                ("Set-Cookie".into(), "tracking=xyz".into()),
+                ("cache-control".into(), "public".into()),
+                ("x-powered-by".into(), "php".into()),
+            ],
+            status: 200,
+            body: vec![],
+            request_path: "/".into(),
+            request_authority: "foo.com".into(),
+            request_host: "foo.com".into(),
+            request_scheme: "https".into(),
+            request_version: "HTTP/1.1".into(),
+            request_headers: vec![],
+            request_method: "GET".into(),
+        };
+        rewrite
+            .rewrite_resp(&mut resp)
+            .expect("should rewrite the response");
+        let headers: Vec<(&str, &str)> = resp
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            headers,
+            vec![
+                ("content-type", "application/json"),
+                ("Cache-Control", "no-store"),
+                ("Set-Cookie", "session=abc"),
+                ("x-debug", "1"),
+                ("set-cookie", "debug=1"),
+            ]
+        );
+
+        let invalid = Rewrite::Headers(HeadersRewrite {
+            headers: vec![HeaderOperation::Append(HeaderEntry {
+                name: "bad header".into(),
+                value: "1".into(),
+            })],
+        });
+        assert!(invalid.rewrite_resp(&mut resp).is_err());
+    }
 }
@@ -1,5 +1,5 @@
 mod config;
-use config::intercept::InterceptConfig;
+use config::intercept::{request_port, InterceptConfig};
 use proxysaur_bindings::{config as proxysaur_config, http::pre_request::ProxyMode};
 
 fn main() {
@@ -14,7 +14,8 @@ fn main() {
     };
 
     let host = proxysaur_bindings::http::pre_request::http_request_get();
-    if config.should_intercept(&host.host) {
+    let port = request_port(&host.authority, &host.scheme);
+    if config.should_intercept(&host.host, port) {
         proxysaur_bindings::http::pre_request::http_set_proxy_mode(ProxyMode::Intercept);
     } else {
         proxysaur_bindings::http::pre_request::http_set_proxy_mode(ProxyMode::Pass);
@@ -1,6 +1,6 @@
 mod config;
-use config::intercept::InterceptConfig;
-use proxysaur_bindings::{config as proxysaur_config, http};
+use config::intercept::{request_port, InterceptConfig};
+use proxysaur_bindings::{config as proxysaur_config, http, log};
 
 fn main() {
     let config_data: Vec<u8> = proxysaur_config::get_config_data();
@@ -13,7 +13,8 @@ fn main() {
         }
     };
     let mut request = http::request::http_request_get().expect("should fetch the request");
-    let host_config = match config.host_config(request.host.as_str()) {
+    let port = request_port(&request.authority, &request.scheme);
+    let host_config = match config.host_config(request.host.as_str(), port) {
         Some(config) => config,
         None => {
             proxysaur_config::set_invalid_data("No host configuration found.");
@@ -21,13 +22,37 @@ fn main() {
         }
     };
 
-    if let Some(redirect) = &host_config.redirect {
+    if let Some((idx, redirect)) = host_config
+        .redirect
+        .iter()
+        .enumerate()
+        .find(|(_idx, redirect)| redirect.should_redirect_request(&request))
+    {
+        log::annotate(&format!("redirect {idx} matched"));
+        if let Some(response) = redirect.response(&request) {
+            let headers: Vec<(&str, &str)> = response
+                .headers
+                .iter()
+                .map(|(n, v)| (n.as_str(), v.as_str()))
+                .collect();
+            if let Err(err) = http::request::http_request_respond(http::request::HttpReply {
+                status: response.status,
+                headers: &headers,
+                body: &response.body,
+            }) {
+                log::annotate(&format!("redirect {idx} failed: {err:?}"));
+            }
+            return;
+        }
         redirect.redirect_request(&mut request);
     }
 
-    for rewrite in &host_config.request_rewrites {
+    for (idx, rewrite) in host_config.request_rewrites.iter().enumerate() {
         if rewrite.should_rewrite_request(&request) {
-            request = rewrite.rewrite(request);
+            log::annotate(&format!("request rewrite {idx} matched"));
+            if let Err(err) = rewrite.rewrite(&mut request) {
+                log::annotate(&format!("request rewrite {idx} failed: {err}"));
+            }
         }
     }
 
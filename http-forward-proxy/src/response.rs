@@ -1,10 +1,11 @@
 mod config;
 
-use config::intercept::InterceptConfig;
+use config::intercept::{request_port, InterceptConfig};
 use config::rewrite::ResponseRewrite;
 use proxysaur_bindings::{
     config as proxysaur_config,
     http::{self, request::HttpRequestResult},
+    log,
 };
 
 fn main() {
@@ -20,7 +21,8 @@ fn main() {
 
     let mut response = http::response::http_response_get().expect("should get the response");
 
-    let host_config = match config.host_config(response.request_host.as_str()) {
+    let port = request_port(&response.request_authority, &response.request_scheme);
+    let host_config = match config.host_config(response.request_host.as_str(), port) {
         Some(config) => config,
         None => {
             proxysaur_config::set_invalid_data("No host configuration found.");
@@ -40,13 +42,17 @@ fn main() {
     };
 
     let response_rewrites = &host_config.response_rewrites;
-    let resp_rewrites: Vec<&ResponseRewrite> = response_rewrites
+    let resp_rewrites: Vec<(usize, &ResponseRewrite)> = response_rewrites
         .iter()
-        .filter(|rewrite| rewrite.should_rewrite_response(&request))
+        .enumerate()
+        .filter(|(_idx, rewrite)| rewrite.should_rewrite_response(&request, &response))
         .collect();
 
-    for rewrite in resp_rewrites.iter() {
-        rewrite.rewrite(&mut response);
+    for (idx, rewrite) in resp_rewrites.iter() {
+        log::annotate(&format!("response rewrite {idx} matched"));
+        if let Err(err) = rewrite.rewrite(&mut response) {
+            log::annotate(&format!("response rewrite {idx} failed: {err}"));
+        }
     }
     let headers: Vec<(&str, &str)> = response
         .headers
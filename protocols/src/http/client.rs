@@ -0,0 +1,216 @@
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use config::HttpClientPolicy;
+use http::{Request, Uri};
+use hyper::{client::HttpConnector, Body};
+use hyper_tls::HttpsConnector;
+use proxysaur_wit_bindings::http::http_client;
+use tokio::runtime::Handle;
+
+use super::{flow::next_flow_id, hostname::Hostname};
+
+pub(crate) type Client = hyper::Client<HttpsConnector<HttpConnector>, Body>;
+
+/// Sends the requests a module makes through the `http-client` interface, as long as the
+/// module's policy allows the destination.
+#[derive(Clone)]
+pub struct ModuleHttpClient {
+    client: Client,
+    policy: HttpClientPolicy,
+}
+
+impl Default for ModuleHttpClient {
+    fn default() -> Self {
+        let client = hyper::Client::builder().build::<_, Body>(HttpsConnector::new());
+        Self::new(client, HttpClientPolicy::default())
+    }
+}
+
+impl ModuleHttpClient {
+    pub fn new(client: Client, policy: HttpClientPolicy) -> Self {
+        Self { client, policy }
+    }
+
+    /// The same client, restricted by another module's policy.
+    pub(crate) fn with_policy(&self, policy: HttpClientPolicy) -> Self {
+        Self::new(self.client.clone(), policy)
+    }
+
+    fn allows(&self, hostname: &Hostname) -> bool {
+        self.policy.allowed_hosts.iter().any(|allowed| {
+            let allowed = allowed.to_ascii_lowercase();
+            allowed == hostname.host || allowed == hostname.authority
+        })
+    }
+
+    fn build_request(
+        &self,
+        request: http_client::HttpClientRequest<'_>,
+    ) -> Result<Request<Body>, http_client::Error> {
+        let uri = Uri::from_str(request.uri).map_err(|err| format!("Invalid URI: {err}"))?;
+        let authority = uri
+            .authority()
+            .ok_or_else(|| format!("URI must be absolute: {uri}"))?;
+        let hostname = Hostname::parse(authority.as_str(), uri.scheme_str().unwrap_or("http"))
+            .map_err(|err| format!("Invalid URI: {err}"))?;
+        if !self.allows(&hostname) {
+            tracing::warn!(destination = %hostname.authority, "Module tried to reach a destination that isn't allowed.");
+            return Err(format!(
+                "{} is not an allowed destination",
+                hostname.authority
+            ));
+        }
+
+        let mut builder = Request::builder().method(request.method).uri(uri);
+        for (name, value) in request.headers {
+            builder = builder.header(name, value);
+        }
+        builder
+            .body(Body::from(request.body.to_vec()))
+            .map_err(|err| format!("Invalid request: {err}"))
+    }
+}
+
+impl http_client::HttpClient for ModuleHttpClient {
+    fn http_client_send(
+        &mut self,
+        request: http_client::HttpClientRequest<'_>,
+    ) -> Result<http_client::HttpReply, http_client::Error> {
+        let request = self.build_request(request)?;
+        let span = tracing::info_span!(
+            "child_flow",
+            id = next_flow_id(),
+            method = %request.method(),
+            uri = %request.uri(),
+        );
+        let _entered = span.enter();
+
+        let started = Instant::now();
+        let timeout_ms = self.policy.timeout_ms;
+        let client = self.client.clone();
+        // Modules run on the blocking pool, so waiting on the runtime here is fine
+        let result = Handle::current().block_on(tokio::time::timeout(
+            Duration::from_millis(timeout_ms),
+            async move {
+                let response = client.request(request).await?;
+                let (parts, body) = response.into_parts();
+                let body = hyper::body::to_bytes(body).await?;
+                Ok::<_, hyper::Error>((parts, body))
+            },
+        ));
+        let (parts, body) = match result {
+            Ok(Ok(response)) => response,
+            Ok(Err(err)) => {
+                tracing::warn!(%err, "Child flow failed.");
+                return Err(format!("Request failed: {err}"));
+            }
+            Err(_) => {
+                tracing::warn!(timeout_ms, "Child flow timed out.");
+                return Err(format!("Request timed out after {timeout_ms}ms"));
+            }
+        };
+        tracing::info!(
+            status = parts.status.as_u16(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "Completed child flow."
+        );
+
+        let headers = parts
+            .headers
+            .iter()
+            .flat_map(|(name, value)| match value.to_str() {
+                Ok(value) => Some((name.to_string(), value.to_string())),
+                Err(_) => None,
+            })
+            .collect();
+        Ok(http_client::HttpReply {
+            status: parts.status.as_u16(),
+            headers,
+            body: body.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::Infallible;
+
+    use config::HttpClientPolicy;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Response, Server,
+    };
+    use proxysaur_wit_bindings::http::http_client::{HttpClient, HttpClientRequest};
+
+    use super::ModuleHttpClient;
+
+    fn request(uri: &str) -> HttpClientRequest<'_> {
+        HttpClientRequest {
+            method: "GET",
+            uri,
+            headers: vec![("x-module", "test")],
+            body: &[],
+        }
+    }
+
+    fn client(allowed_hosts: Vec<String>) -> ModuleHttpClient {
+        let policy = HttpClientPolicy {
+            allowed_hosts,
+            ..HttpClientPolicy::default()
+        };
+        ModuleHttpClient::new(ModuleHttpClient::default().client, policy)
+    }
+
+    #[test]
+    fn denies_by_default() {
+        let mut client = ModuleHttpClient::default();
+        let err = client
+            .http_client_send(request("http://localhost:9000/token"))
+            .expect_err("should deny the request");
+        assert_eq!(err, "localhost:9000 is not an allowed destination");
+    }
+
+    #[test]
+    fn allows_hosts_and_authorities() {
+        let client = client(vec!["auth.local".into(), "localhost:9000".into()]);
+        assert!(client.build_request(request("http://auth.local/")).is_ok());
+        assert!(client
+            .build_request(request("https://AUTH.local:8443/"))
+            .is_ok());
+        assert!(client
+            .build_request(request("http://localhost:9000/"))
+            .is_ok());
+        assert!(client
+            .build_request(request("http://localhost:9001/"))
+            .is_err());
+        assert!(client.build_request(request("/relative")).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sends_allowed_requests() {
+        let make_service = make_service_fn(|_conn| async {
+            Ok::<_, Infallible>(service_fn(|req: hyper::Request<Body>| async move {
+                let header = req.headers()["x-module"].clone();
+                Ok::<_, Infallible>(Response::new(Body::from(format!(
+                    "hello {}",
+                    header.to_str().unwrap_or_default()
+                ))))
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let address = server.local_addr();
+        tokio::spawn(server);
+
+        let mut client = client(vec![address.to_string()]);
+        let uri = format!("http://{address}/");
+        let reply = tokio::task::spawn_blocking(move || client.http_client_send(request(&uri)))
+            .await
+            .expect("should run the request")
+            .expect("should send the request");
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body, b"hello test");
+    }
+}
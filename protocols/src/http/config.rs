@@ -1,4 +1,4 @@
-use config::Proxy;
+use config::{Proxy, WasiModule};
 use proxysaur_wit_bindings::config::config::Config;
 
 pub(crate) struct ProxyConfig {
@@ -6,6 +6,18 @@ pub(crate) struct ProxyConfig {
     pub(crate) error: String,
 }
 
+impl ProxyConfig {
+    /// What `module` sees of the proxy, with its own configuration in place of the proxy's.
+    pub(crate) fn for_module(proxy: &Proxy, module: &WasiModule) -> Self {
+        let mut proxy = proxy.clone();
+        proxy.wasi_configuration_bytes = module.configuration_bytes.clone();
+        Self {
+            proxy,
+            error: "".into(),
+        }
+    }
+}
+
 impl Config for ProxyConfig {
     fn get_config_data(&mut self) -> Vec<u8> {
         match &self.proxy.wasi_configuration_bytes {
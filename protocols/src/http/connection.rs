@@ -0,0 +1,22 @@
+use std::net::SocketAddr;
+
+use config::Proxy;
+use tokio::net::TcpStream;
+
+/// Describes the client connection a request arrived on.
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub client_address: SocketAddr,
+    pub listener_name: String,
+    pub listener_port: u16,
+}
+
+impl Connection {
+    pub fn new(socket: &TcpStream, proxy: &Proxy) -> std::io::Result<Self> {
+        Ok(Self {
+            client_address: socket.peer_addr()?,
+            listener_name: proxy.listener_name(),
+            listener_port: socket.local_addr()?.port(),
+        })
+    }
+}
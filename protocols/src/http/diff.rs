@@ -0,0 +1,362 @@
+//! What each module or script changed about the request or response passing through its hook.
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use proxysaur_wit_bindings::http::{request::HttpRequestResult, response::HttpResponse};
+
+/// Past this many pairs of lines to compare, changed sections of a body are shown as removed
+/// and added in full rather than diffed.
+const MAX_DIFF_CELLS: usize = 1_000_000;
+
+/// A line diff of `before` and `after`, marking unchanged lines with two spaces, removed lines
+/// with `- ` and added ones with `+ `.
+pub fn line_diff<S: AsRef<str> + PartialEq>(before: &[S], after: &[S]) -> Vec<String> {
+    let prefix = before
+        .iter()
+        .zip(after.iter())
+        .take_while(|(before, after)| before == after)
+        .count();
+    let suffix = before[prefix..]
+        .iter()
+        .rev()
+        .zip(after[prefix..].iter().rev())
+        .take_while(|(before, after)| before == after)
+        .count();
+    let removed = &before[prefix..before.len() - suffix];
+    let added = &after[prefix..after.len() - suffix];
+
+    let mut lines: Vec<String> = before[..prefix]
+        .iter()
+        .map(|line| format!("  {}", line.as_ref()))
+        .collect();
+    if (removed.len() + 1).saturating_mul(added.len() + 1) > MAX_DIFF_CELLS {
+        lines.extend(removed.iter().map(|line| format!("- {}", line.as_ref())));
+        lines.extend(added.iter().map(|line| format!("+ {}", line.as_ref())));
+    } else {
+        lines.extend(lcs_diff(removed, added));
+    }
+    lines.extend(
+        before[before.len() - suffix..]
+            .iter()
+            .map(|line| format!("  {}", line.as_ref())),
+    );
+    lines
+}
+
+fn lcs_diff<S: AsRef<str> + PartialEq>(before: &[S], after: &[S]) -> Vec<String> {
+    // Longest common subsequence of the lines after each position
+    let mut lcs = vec![vec![0usize; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            lcs[i][j] = if before[i] == after[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < before.len() || j < after.len() {
+        if i < before.len() && j < after.len() && before[i] == after[j] {
+            lines.push(format!("  {}", before[i].as_ref()));
+            i += 1;
+            j += 1;
+        } else if j == after.len() || (i < before.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(format!("- {}", before[i].as_ref()));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", after[j].as_ref()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// A request or response as a hook sees it, to compare before and after each stage.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Snapshot {
+    method: Option<String>,
+    uri: Option<String>,
+    status: Option<u16>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Snapshot {
+    pub(crate) fn request(request: &HttpRequestResult) -> Self {
+        Self {
+            method: Some(request.method.clone()),
+            uri: Some(format!(
+                "{}://{}{}",
+                request.scheme, request.authority, request.path
+            )),
+            status: None,
+            headers: request.headers.clone(),
+            body: request.body.clone(),
+        }
+    }
+
+    pub(crate) fn response(response: &HttpResponse) -> Self {
+        Self {
+            method: None,
+            uri: None,
+            status: Some(response.status),
+            headers: response.headers.clone(),
+            body: response.body.clone(),
+        }
+    }
+}
+
+/// How a body changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BodyDiff {
+    /// The changed lines of a text body, marked like [`line_diff`]
+    Text(Vec<String>),
+    /// The sizes before and after of a body that isn't UTF-8
+    Binary { before: usize, after: usize },
+}
+
+/// What a single module or script changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HookDiff {
+    /// `request` or `response`
+    pub hook: String,
+    /// The path of the module or script
+    pub stage: PathBuf,
+    pub method: Option<(String, String)>,
+    pub uri: Option<(String, String)>,
+    pub status: Option<(u16, u16)>,
+    /// The status a request stage answered the request with
+    pub responded: Option<u16>,
+    pub headers_added: Vec<(String, String)>,
+    pub headers_removed: Vec<(String, String)>,
+    /// Headers by lowercase name, with their values before and after
+    pub headers_modified: Vec<(String, String, String)>,
+    pub body: Option<BodyDiff>,
+}
+
+/// The values of each header by lowercase name, in order.
+fn grouped_headers(headers: &[(String, String)]) -> BTreeMap<String, Vec<&str>> {
+    let mut grouped: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for (name, value) in headers.iter() {
+        grouped
+            .entry(name.to_lowercase())
+            .or_default()
+            .push(value.as_str());
+    }
+    grouped
+}
+
+fn changed<T: PartialEq + Clone>(before: &Option<T>, after: &Option<T>) -> Option<(T, T)> {
+    match (before, after) {
+        (Some(before), Some(after)) if before != after => Some((before.clone(), after.clone())),
+        _ => None,
+    }
+}
+
+impl HookDiff {
+    pub(crate) fn between(hook: &str, stage: &Path, before: &Snapshot, after: &Snapshot) -> Self {
+        let before_headers = grouped_headers(&before.headers);
+        let after_headers = grouped_headers(&after.headers);
+        let mut diff = Self {
+            hook: hook.to_string(),
+            stage: stage.to_path_buf(),
+            method: changed(&before.method, &after.method),
+            uri: changed(&before.uri, &after.uri),
+            status: changed(&before.status, &after.status),
+            ..Self::default()
+        };
+
+        for (name, before_values) in before_headers.iter() {
+            match after_headers.get(name) {
+                None => diff.headers_removed.extend(
+                    before_values
+                        .iter()
+                        .map(|value| (name.clone(), value.to_string())),
+                ),
+                Some(after_values) if after_values != before_values => {
+                    diff.headers_modified.push((
+                        name.clone(),
+                        before_values.join(", "),
+                        after_values.join(", "),
+                    ))
+                }
+                Some(_after_values) => {}
+            }
+        }
+        for (name, after_values) in after_headers.iter() {
+            if !before_headers.contains_key(name) {
+                diff.headers_added.extend(
+                    after_values
+                        .iter()
+                        .map(|value| (name.clone(), value.to_string())),
+                );
+            }
+        }
+
+        if before.body != after.body {
+            diff.body = match (
+                std::str::from_utf8(&before.body),
+                std::str::from_utf8(&after.body),
+            ) {
+                (Ok(before), Ok(after)) => {
+                    let before: Vec<&str> = before.lines().collect();
+                    let after: Vec<&str> = after.lines().collect();
+                    let changes = line_diff(&before, &after)
+                        .into_iter()
+                        .filter(|line| !line.starts_with("  "))
+                        .collect();
+                    Some(BodyDiff::Text(changes))
+                }
+                _ => Some(BodyDiff::Binary {
+                    before: before.body.len(),
+                    after: after.body.len(),
+                }),
+            };
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.method.is_none()
+            && self.uri.is_none()
+            && self.status.is_none()
+            && self.responded.is_none()
+            && self.headers_added.is_empty()
+            && self.headers_removed.is_empty()
+            && self.headers_modified.is_empty()
+            && self.body.is_none()
+    }
+}
+
+impl fmt::Display for HookDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} changed by {}", self.hook, self.stage.display())?;
+        if let Some((before, after)) = self.method.as_ref() {
+            writeln!(f, "method: {before} -> {after}")?;
+        }
+        if let Some((before, after)) = self.uri.as_ref() {
+            writeln!(f, "uri: {before} -> {after}")?;
+        }
+        if let Some((before, after)) = self.status.as_ref() {
+            writeln!(f, "status: {before} -> {after}")?;
+        }
+        if let Some(status) = self.responded {
+            writeln!(f, "responded: {status}")?;
+        }
+        for (name, value) in self.headers_added.iter() {
+            writeln!(f, "+ {name}: {value}")?;
+        }
+        for (name, value) in self.headers_removed.iter() {
+            writeln!(f, "- {name}: {value}")?;
+        }
+        for (name, before, after) in self.headers_modified.iter() {
+            writeln!(f, "~ {name}: {before} -> {after}")?;
+        }
+        match self.body.as_ref() {
+            Some(BodyDiff::Text(lines)) => {
+                writeln!(f, "body:")?;
+                for line in lines.iter() {
+                    writeln!(f, "  {line}")?;
+                }
+            }
+            Some(BodyDiff::Binary { before, after }) => {
+                writeln!(f, "body: {before} bytes -> {after} bytes")?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use proxysaur_wit_bindings::http::response::HttpResponse;
+
+    use super::{line_diff, BodyDiff, HookDiff, Snapshot};
+
+    #[test]
+    fn diffs_lines() {
+        let before = vec!["GET http://localhost/", "accept: */*", "", "hello"];
+        let after = vec!["POST http://localhost/", "accept: */*", "", "hello"];
+        assert_eq!(
+            line_diff(&before, &after),
+            vec![
+                "- GET http://localhost/",
+                "+ POST http://localhost/",
+                "  accept: */*",
+                "  ",
+                "  hello",
+            ]
+        );
+        assert_eq!(
+            line_diff(&["a", "b", "c"], &["a", "x", "c", "d"]),
+            vec!["  a", "- b", "+ x", "  c", "+ d"]
+        );
+    }
+
+    fn response(status: u16, headers: &[(&str, &str)], body: &[u8]) -> HttpResponse {
+        HttpResponse {
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            status,
+            body: body.to_vec(),
+            request_path: "/".into(),
+            request_authority: "localhost:80".into(),
+            request_host: "localhost".into(),
+            request_scheme: "http".into(),
+            request_version: "HTTP/1.1".into(),
+            request_headers: vec![],
+            request_method: "GET".into(),
+        }
+    }
+
+    #[test]
+    fn diffs_responses() {
+        let before = Snapshot::response(&response(
+            500,
+            &[
+                ("Content-Type", "text/plain"),
+                ("Set-Cookie", "a=1"),
+                ("X-Old", "1"),
+            ],
+            b"line one\nerror\nline three",
+        ));
+        let after = Snapshot::response(&response(
+            200,
+            &[
+                ("content-type", "text/plain"),
+                ("set-cookie", "a=1"),
+                ("set-cookie", "b=2"),
+                ("x-new", "1"),
+            ],
+            b"line one\nok\nline three",
+        ));
+        let diff = HookDiff::between("response", Path::new("/modules/fix.wasm"), &before, &after);
+        assert_eq!(diff.status, Some((500, 200)));
+        assert_eq!(diff.headers_added, vec![("x-new".into(), "1".into())]);
+        assert_eq!(diff.headers_removed, vec![("x-old".into(), "1".into())]);
+        assert_eq!(
+            diff.headers_modified,
+            vec![("set-cookie".into(), "a=1".into(), "a=1, b=2".into())]
+        );
+        assert_eq!(
+            diff.body,
+            Some(BodyDiff::Text(vec!["- error".into(), "+ ok".into()]))
+        );
+        assert!(diff.to_string().contains("status: 500 -> 200"));
+
+        let unchanged =
+            HookDiff::between("response", Path::new("/modules/fix.wasm"), &after, &after);
+        assert!(unchanged.is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, PoisonError,
+};
+
+use tracing::Span;
+
+use super::diff::HookDiff;
+
+static NEXT_FLOW_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies a flow in the logs. Requests made by modules on behalf of a flow get their own
+/// ID, and are logged inside the span of the flow that made them.
+pub(crate) fn next_flow_id() -> u64 {
+    NEXT_FLOW_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A request proxied for a client, along with the tags modules have annotated it with and,
+/// when the proxy diffs its hooks, what each stage changed. Clones refer to the same flow.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Flow {
+    /// Zero when there's no flow, like outside of a request
+    pub(crate) id: u64,
+    annotations: Arc<Mutex<Vec<String>>>,
+    changes: Arc<Mutex<Vec<HookDiff>>>,
+}
+
+impl Flow {
+    pub(crate) fn new() -> Self {
+        Self {
+            id: next_flow_id(),
+            annotations: Arc::default(),
+            changes: Arc::default(),
+        }
+    }
+
+    /// The span everything done for the flow is logged in.
+    pub(crate) fn span(&self) -> Span {
+        tracing::info_span!("flow", id = self.id)
+    }
+
+    pub(crate) fn annotate(&self, tag: &str) {
+        let mut annotations = self
+            .annotations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        annotations.push(tag.to_string());
+    }
+
+    pub(crate) fn annotations(&self) -> Vec<String> {
+        self.annotations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub(crate) fn record_change(&self, change: HookDiff) {
+        let mut changes = self.changes.lock().unwrap_or_else(PoisonError::into_inner);
+        changes.push(change);
+    }
+
+    pub(crate) fn changes(&self) -> Vec<HookDiff> {
+        self.changes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
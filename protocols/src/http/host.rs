@@ -0,0 +1,48 @@
+use anyhow::Result;
+use config::WasiModule;
+use proxysaur_wit_bindings::{http::http_client, log::log, pipeline::pipeline, state::state};
+use wasi_runtime::Linker;
+
+use super::{
+    client::ModuleHttpClient, log::ModuleLog, pipeline::PipelineControl, script::ScriptCache,
+    state::StateStore,
+};
+
+/// The interfaces modules can import whichever hook they run in.
+#[derive(Clone, Default)]
+pub struct HostServices {
+    pub http_client: ModuleHttpClient,
+    pub state: StateStore,
+    pub log: ModuleLog,
+    pub pipeline: PipelineControl,
+    pub scripts: ScriptCache,
+}
+
+impl HostServices {
+    /// The services for `module`, the next one in its hook's pipeline.
+    pub(crate) fn for_module(&self, module: &WasiModule) -> Self {
+        Self {
+            http_client: self.http_client.with_policy(module.http_client.clone()),
+            log: self.log.for_module(&module.path),
+            pipeline: PipelineControl::default(),
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn add_to_linker<T>(
+        linker: &mut Linker<T>,
+        get: impl Fn(&mut T) -> &mut HostServices + Send + Sync + Copy + 'static,
+    ) -> Result<()> {
+        http_client::add_to_linker(linker, move |data| -> &mut ModuleHttpClient {
+            &mut get(data).http_client
+        })?;
+        state::add_to_linker(linker, move |data| -> &mut StateStore {
+            &mut get(data).state
+        })?;
+        log::add_to_linker(linker, move |data| -> &mut ModuleLog { &mut get(data).log })?;
+        pipeline::add_to_linker(linker, move |data| -> &mut PipelineControl {
+            &mut get(data).pipeline
+        })?;
+        Ok(())
+    }
+}
@@ -1,49 +1,204 @@
-use http::Request;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use http::{header::HOST, Method, Request};
 use hyper::Body;
+use percent_encoding::percent_decode_str;
 
 #[derive(Debug, Clone)]
 pub struct Hostname {
+    /// Always `host:port`, suitable for opening a connection.
     pub authority: String,
+    /// The ASCII form of the host. Internationalized names are converted to punycode and
+    /// IPv6 literals keep their brackets, ex. `[::1]`.
     pub host: String,
     pub scheme: String,
     pub port: u16,
 }
 
-impl TryFrom<&Request<Body>> for Hostname {
-    type Error = anyhow::Error;
+fn default_port(scheme: &str) -> Option<u16> {
+    match scheme {
+        "http" | "ws" => Some(80),
+        "https" | "wss" => Some(443),
+        _ => None,
+    }
+}
 
-    fn try_from(req: &Request<Body>) -> Result<Self, Self::Error> {
-        let host = req
-            .uri()
-            .authority()
-            .ok_or_else(|| anyhow::Error::msg("Missing authority"))?;
-        let authority = host.as_str().to_string();
-        let parts: Vec<&str> = host.as_str().split(':').collect();
-        let port: Option<u16> = if parts.len() > 1 {
-            match parts[1].parse::<u16>() {
-                Ok(port) => Some(port),
-                Err(_) => None,
-            }
-        } else {
-            None
-        };
-        let port: u16 = match port {
-            Some(v) => v,
-            None => match req.uri().scheme_str() {
-                Some("http") => 80,
-                Some("https") => 443,
-                Some(_) | None => {
-                    tracing::error!(%authority, "Hostname missing port.");
-                    return Err(anyhow::Error::msg("Missing port"));
-                }
+/// Splits an authority into its host and optional port, dropping any user info.
+fn split_authority(authority: &str) -> Result<(&str, Option<u16>), anyhow::Error> {
+    let authority = authority.rsplit('@').next().unwrap_or(authority);
+    let (host, port) = if authority.starts_with('[') {
+        let end = authority
+            .find(']')
+            .ok_or_else(|| anyhow::Error::msg("Unterminated IPv6 literal"))?;
+        let (host, rest) = authority.split_at(end + 1);
+        match rest {
+            "" => (host, None),
+            rest => match rest.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => return Err(anyhow::Error::msg("Invalid characters after IPv6 literal")),
             },
+        }
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        }
+    };
+    let port = match port {
+        Some("") | None => None,
+        Some(port) => Some(
+            port.parse::<u16>()
+                .map_err(|_| anyhow::Error::msg(format!("Invalid port: {port}")))?,
+        ),
+    };
+    Ok((host, port))
+}
+
+/// Converts the host into the ASCII form used for connecting and issuing certificates.
+fn normalize_host(host: &str) -> Result<String, anyhow::Error> {
+    if let Some(literal) = host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        let addr: Ipv6Addr = literal
+            .parse()
+            .map_err(|_| anyhow::Error::msg(format!("Invalid IPv6 address: {literal}")))?;
+        return Ok(format!("[{addr}]"));
+    }
+    if host.parse::<Ipv4Addr>().is_ok() {
+        return Ok(host.to_string());
+    }
+    let decoded = percent_decode_str(host).decode_utf8()?;
+    let ascii = idna::domain_to_ascii(&decoded)
+        .map_err(|err| anyhow::Error::msg(format!("Invalid host {host}: {err:?}")))?;
+    if ascii.is_empty() {
+        return Err(anyhow::Error::msg("Missing host"));
+    }
+    Ok(ascii)
+}
+
+impl Hostname {
+    pub fn parse(authority: &str, scheme: &str) -> Result<Self, anyhow::Error> {
+        let scheme = scheme.to_ascii_lowercase();
+        let (host, port) = split_authority(authority)?;
+        let host = normalize_host(host)?;
+        let port = match port.or_else(|| default_port(&scheme)) {
+            Some(port) => port,
+            None => {
+                tracing::error!(%authority, %scheme, "Hostname missing port.");
+                return Err(anyhow::Error::msg("Missing port"));
+            }
         };
-        let host = parts[0].to_string();
         Ok(Hostname {
-            authority,
+            authority: format!("{host}:{port}"),
             host,
+            scheme,
             port,
-            scheme: "https".to_string(),
         })
     }
+
+    /// The host without IPv6 brackets, as it appears in a certificate.
+    pub fn server_name(&self) -> &str {
+        self.host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(&self.host)
+    }
+}
+
+impl TryFrom<&Request<Body>> for Hostname {
+    type Error = anyhow::Error;
+
+    fn try_from(req: &Request<Body>) -> Result<Self, Self::Error> {
+        let authority = match req.uri().authority() {
+            Some(authority) => authority.as_str(),
+            None => req
+                .headers()
+                .get(HOST)
+                .and_then(|host| host.to_str().ok())
+                .ok_or_else(|| anyhow::Error::msg("Missing authority"))?,
+        };
+        // CONNECT requests only carry an authority, so the scheme comes from the port: port 80
+        // tunnels plain HTTP, anything else is assumed to carry TLS
+        let scheme = match req.uri().scheme_str() {
+            Some(scheme) => scheme,
+            None if req.method() == Method::CONNECT => match split_authority(authority)?.1 {
+                Some(80) => "http",
+                _ => "https",
+            },
+            None => "http",
+        };
+        Hostname::parse(authority, scheme)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Hostname;
+    use http::Request;
+    use hyper::Body;
+
+    fn hostname(method: &str, uri: &str) -> Hostname {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .expect("should build the request");
+        Hostname::try_from(&req).expect("should parse the hostname")
+    }
+
+    #[test]
+    fn connect_defaults_to_https() {
+        let hostname = hostname("CONNECT", "example.com");
+        assert_eq!(hostname.scheme, "https");
+        assert_eq!(hostname.port, 443);
+        assert_eq!(hostname.authority, "example.com:443");
+    }
+
+    #[test]
+    fn connect_to_port_80_is_plain_http() {
+        let hostname = hostname("CONNECT", "example.com:80");
+        assert_eq!(hostname.scheme, "http");
+        assert_eq!(hostname.port, 80);
+        assert_eq!(hostname.authority, "example.com:80");
+    }
+
+    #[test]
+    fn absolute_form_keeps_scheme() {
+        let hostname = hostname("GET", "http://example.com/path?query=1");
+        assert_eq!(hostname.scheme, "http");
+        assert_eq!(hostname.host, "example.com");
+        assert_eq!(hostname.port, 80);
+    }
+
+    #[test]
+    fn ipv6_literal() {
+        let hostname = hostname("CONNECT", "[::1]:8443");
+        assert_eq!(hostname.host, "[::1]");
+        assert_eq!(hostname.server_name(), "::1");
+        assert_eq!(hostname.port, 8443);
+        assert_eq!(hostname.authority, "[::1]:8443");
+    }
+
+    #[test]
+    fn internationalized_name() {
+        let hostname = Hostname::parse("Bücher.example:8080", "https").expect("should parse");
+        assert_eq!(hostname.host, "xn--bcher-kva.example");
+        assert_eq!(hostname.authority, "xn--bcher-kva.example:8080");
+    }
+
+    #[test]
+    fn falls_back_to_host_header() {
+        let req = Request::builder()
+            .uri("/path")
+            .header("host", "example.com:8000")
+            .body(Body::empty())
+            .expect("should build the request");
+        let hostname = Hostname::try_from(&req).expect("should parse the hostname");
+        assert_eq!(hostname.authority, "example.com:8000");
+        assert_eq!(hostname.scheme, "http");
+    }
+
+    #[test]
+    fn unknown_scheme_requires_port() {
+        assert!(Hostname::parse("example.com", "gopher").is_err());
+        assert!(Hostname::parse("example.com:70", "gopher").is_ok());
+    }
 }
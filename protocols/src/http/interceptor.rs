@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use config::Proxy;
+use hyper::{Body, Request, Response};
+use wasi_runtime::WasiRuntime;
+
+use super::{
+    pre_request::{process_pre_request, ProxyHttpPreRequest},
+    proxy::HttpContext,
+    request::process_request,
+    response::process_response,
+};
+
+pub use super::{
+    connection::Connection, hostname::Hostname, pre_request::PreRequestAction,
+    request::RequestOutcome, response::RequestLine,
+};
+
+/// Decides what the proxy does with the requests passing through it. Every method defaults to
+/// leaving things as they are, so implementations only need the hooks they care about.
+#[async_trait]
+pub trait HttpInterceptor: Send + Sync + 'static {
+    /// Called before anything is sent upstream by forward proxies, to decide whether the
+    /// request is intercepted, passed through, tunneled elsewhere or blocked.
+    async fn pre_request(
+        &self,
+        _req: &Request<Body>,
+        _hostname: &Hostname,
+        _connection: &Connection,
+        _proxy: &Proxy,
+        _context: &HttpContext,
+    ) -> Result<PreRequestAction> {
+        Ok(PreRequestAction::Pass)
+    }
+
+    /// Modifies an intercepted request before it's sent upstream, or answers it directly.
+    async fn on_request(
+        &self,
+        req: Request<Body>,
+        _proxy: &Proxy,
+        _context: &HttpContext,
+    ) -> Result<RequestOutcome> {
+        Ok(RequestOutcome::Forward(req))
+    }
+
+    /// Modifies the response to an intercepted request before it's returned to the client.
+    async fn on_response(
+        &self,
+        resp: Response<Body>,
+        _request_line: RequestLine,
+        _proxy: &Proxy,
+        _context: &HttpContext,
+    ) -> Result<Response<Body>> {
+        Ok(resp)
+    }
+}
+
+/// Leaves every request alone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PassThrough;
+
+impl HttpInterceptor for PassThrough {}
+
+type PreRequestFn = dyn Fn(&Request<Body>, &Hostname) -> PreRequestAction + Send + Sync;
+type OnRequestFn = dyn Fn(Request<Body>) -> RequestOutcome + Send + Sync;
+type OnResponseFn = dyn Fn(Response<Body>, &RequestLine) -> Response<Body> + Send + Sync;
+
+/// Intercepts with plain closures, for simple rules and tests that don't need WASM. Hooks
+/// without a closure leave things as they are.
+#[derive(Clone, Default)]
+pub struct FnInterceptor {
+    pre_request: Option<Arc<PreRequestFn>>,
+    on_request: Option<Arc<OnRequestFn>>,
+    on_response: Option<Arc<OnResponseFn>>,
+}
+
+impl FnInterceptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decides what forward proxies do with each request with `pre_request`.
+    pub fn with_pre_request<F>(mut self, pre_request: F) -> Self
+    where
+        F: Fn(&Request<Body>, &Hostname) -> PreRequestAction + Send + Sync + 'static,
+    {
+        self.pre_request = Some(Arc::new(pre_request));
+        self
+    }
+
+    /// Modifies or answers intercepted requests with `on_request`.
+    pub fn with_on_request<F>(mut self, on_request: F) -> Self
+    where
+        F: Fn(Request<Body>) -> RequestOutcome + Send + Sync + 'static,
+    {
+        self.on_request = Some(Arc::new(on_request));
+        self
+    }
+
+    /// Modifies the responses to intercepted requests with `on_response`.
+    pub fn with_on_response<F>(mut self, on_response: F) -> Self
+    where
+        F: Fn(Response<Body>, &RequestLine) -> Response<Body> + Send + Sync + 'static,
+    {
+        self.on_response = Some(Arc::new(on_response));
+        self
+    }
+}
+
+#[async_trait]
+impl HttpInterceptor for FnInterceptor {
+    async fn pre_request(
+        &self,
+        req: &Request<Body>,
+        hostname: &Hostname,
+        _connection: &Connection,
+        _proxy: &Proxy,
+        _context: &HttpContext,
+    ) -> Result<PreRequestAction> {
+        Ok(match &self.pre_request {
+            Some(pre_request) => pre_request(req, hostname),
+            None => PreRequestAction::Pass,
+        })
+    }
+
+    async fn on_request(
+        &self,
+        req: Request<Body>,
+        _proxy: &Proxy,
+        _context: &HttpContext,
+    ) -> Result<RequestOutcome> {
+        Ok(match &self.on_request {
+            Some(on_request) => on_request(req),
+            None => RequestOutcome::Forward(req),
+        })
+    }
+
+    async fn on_response(
+        &self,
+        resp: Response<Body>,
+        request_line: RequestLine,
+        _proxy: &Proxy,
+        _context: &HttpContext,
+    ) -> Result<Response<Body>> {
+        Ok(match &self.on_response {
+            Some(on_response) => on_response(resp, &request_line),
+            None => resp,
+        })
+    }
+}
+
+/// Runs each hook's pipeline of WASM modules, followed by its script, as configured on the proxy.
+#[derive(Clone)]
+pub struct WasmInterceptor {
+    wasi_runtime: WasiRuntime,
+}
+
+impl WasmInterceptor {
+    pub fn new(wasi_runtime: WasiRuntime) -> Self {
+        Self { wasi_runtime }
+    }
+}
+
+#[async_trait]
+impl HttpInterceptor for WasmInterceptor {
+    async fn pre_request(
+        &self,
+        req: &Request<Body>,
+        hostname: &Hostname,
+        connection: &Connection,
+        proxy: &Proxy,
+        context: &HttpContext,
+    ) -> Result<PreRequestAction> {
+        let pre_request = ProxyHttpPreRequest::new(req, hostname, connection);
+        process_pre_request(
+            &mut self.wasi_runtime.clone(),
+            pre_request,
+            proxy.pre_request_pipeline(),
+            proxy.clone(),
+            context.host_services(),
+        )
+        .await
+    }
+
+    async fn on_request(
+        &self,
+        req: Request<Body>,
+        proxy: &Proxy,
+        context: &HttpContext,
+    ) -> Result<RequestOutcome> {
+        let scheme = if proxy.tls { "https" } else { "http" };
+        process_request(
+            &mut self.wasi_runtime.clone(),
+            req,
+            proxy.request_pipeline(),
+            scheme,
+            proxy.upstream_address().as_str(),
+            proxy.clone(),
+            context.host_services(),
+        )
+        .await
+    }
+
+    async fn on_response(
+        &self,
+        resp: Response<Body>,
+        request_line: RequestLine,
+        proxy: &Proxy,
+        context: &HttpContext,
+    ) -> Result<Response<Body>> {
+        process_response(
+            &mut self.wasi_runtime.clone(),
+            resp,
+            proxy.response_pipeline(),
+            proxy.clone(),
+            request_line,
+            context.host_services(),
+        )
+        .await
+    }
+}
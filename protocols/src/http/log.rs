@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use proxysaur_wit_bindings::log::log::{self, LogLevel};
+
+use super::{diff::HookDiff, flow::Flow};
+
+/// Feeds what a module logs through the `log` interface into tracing, tagged with the module
+/// it came from. Modules run inside the span of their flow, so events carry the flow ID too.
+#[derive(Clone, Debug, Default)]
+pub struct ModuleLog {
+    module: String,
+    flow: Flow,
+}
+
+impl ModuleLog {
+    pub(crate) fn new(flow: Flow) -> Self {
+        Self {
+            module: String::new(),
+            flow,
+        }
+    }
+
+    /// The log of the module at `path`, in the same flow.
+    pub(crate) fn for_module(&self, path: &Path) -> Self {
+        let module = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self {
+            module,
+            flow: self.flow.clone(),
+        }
+    }
+}
+
+impl ModuleLog {
+    /// Logs what a stage of a hook changed, and keeps it with the flow.
+    pub(crate) fn record_change(&self, change: HookDiff) {
+        if change.is_empty() {
+            return;
+        }
+        tracing::info!(
+            hook = change.hook.as_str(),
+            stage = ?change.stage,
+            "Hook stage changed the {}:\n{change}",
+            change.hook
+        );
+        self.flow.record_change(change);
+    }
+}
+
+impl log::Log for ModuleLog {
+    fn log(&mut self, level: LogLevel, message: &str, fields: log::LogFields<'_>) {
+        let fields = fields
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let fields = fields.as_str();
+        let module = self.module.as_str();
+        match level {
+            LogLevel::Trace => tracing::trace!(module, fields, "{message}"),
+            LogLevel::Debug => tracing::debug!(module, fields, "{message}"),
+            LogLevel::Info => tracing::info!(module, fields, "{message}"),
+            LogLevel::Warn => tracing::warn!(module, fields, "{message}"),
+            LogLevel::Error => tracing::error!(module, fields, "{message}"),
+        }
+    }
+
+    fn annotate(&mut self, tag: &str) {
+        tracing::info!(module = self.module.as_str(), tag, "Annotated flow.");
+        self.flow.annotate(tag);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use proxysaur_wit_bindings::log::log::Log;
+
+    use super::{Flow, ModuleLog};
+
+    #[test]
+    fn annotates_the_flow() {
+        let flow = Flow::new();
+        let log = ModuleLog::new(flow.clone());
+        let mut request_log = log.for_module(Path::new("/modules/request.wasm"));
+        let mut response_log = log.for_module(Path::new("/modules/response.wasm"));
+        assert_eq!(request_log.module, "request");
+
+        request_log.annotate("rule api-mock matched");
+        response_log.annotate("cached");
+        assert_eq!(flow.annotations(), vec!["rule api-mock matched", "cached"]);
+        assert!(Flow::new().annotations().is_empty());
+    }
+}
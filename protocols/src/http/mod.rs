@@ -1,13 +1,24 @@
 use http::Version;
 use thiserror::Error;
 
+mod client;
 mod config;
+mod connection;
+mod flow;
+mod host;
 mod hostname;
+mod log;
+mod pipeline;
 mod pre_request;
 mod request;
 mod response;
+mod script;
 
+pub mod diff;
+pub mod interceptor;
 pub mod proxy;
+pub mod runner;
+pub mod state;
 
 #[derive(Error, Debug)]
 pub enum ProxyHttpError {
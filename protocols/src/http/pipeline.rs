@@ -0,0 +1,20 @@
+use proxysaur_wit_bindings::pipeline::pipeline;
+
+/// Lets a module skip the modules after it in its hook's pipeline.
+#[derive(Clone, Debug, Default)]
+pub struct PipelineControl {
+    stopped: bool,
+}
+
+impl PipelineControl {
+    pub(crate) fn stopped(&self) -> bool {
+        self.stopped
+    }
+}
+
+impl pipeline::Pipeline for PipelineControl {
+    fn pipeline_stop(&mut self) {
+        tracing::debug!("Module stopped the pipeline.");
+        self.stopped = true;
+    }
+}
@@ -1,18 +1,61 @@
-use std::path::PathBuf;
-
 use anyhow::Result;
-use config::Proxy;
+use config::{FailurePolicy, Proxy, WasiModule};
+use http::{Method, Request, StatusCode};
+use hyper::Body;
 use proxysaur_wit_bindings::config::config::add_to_linker;
-use proxysaur_wit_bindings::http::pre_request;
-pub use proxysaur_wit_bindings::http::pre_request::ProxyMode;
-use wasi_runtime::{Linker, Store, WasiCtx, WasiCtxBuilder, WasiRuntime};
+use proxysaur_wit_bindings::http::pre_request::{self, ProxyMode};
+use wasi_runtime::{build_wasi_ctx, Store, StoreLimits, WasiCtx, WasiRuntime};
+
+use super::{
+    config::ProxyConfig, connection::Connection, host::HostServices, hostname::Hostname,
+    script::run_pre_request_script,
+};
+
+/// What the proxy should do with a request once the pre-request module has run.
+#[derive(Debug, Clone)]
+pub enum PreRequestAction {
+    Intercept,
+    Pass,
+    Block(StatusCode),
+    TunnelTo(Hostname),
+    /// The module failed and the proxy fails closed, carrying the trap message.
+    Fail(String),
+}
+
+impl PreRequestAction {
+    fn precedence(&self) -> u8 {
+        match self {
+            PreRequestAction::Pass => 0,
+            PreRequestAction::Intercept => 1,
+            PreRequestAction::TunnelTo(_) => 2,
+            PreRequestAction::Block(_) => 3,
+            PreRequestAction::Fail(_) => 4,
+        }
+    }
 
-use super::{config::ProxyConfig, hostname::Hostname};
+    /// Combines the decisions of two modules in a pipeline, where `self` came first. The more
+    /// restrictive decision wins: a failure over a block, over a tunnel, over intercepting, over
+    /// passing through. When both make the same kind of decision, the earlier module's stands.
+    pub fn combine(self, next: PreRequestAction) -> PreRequestAction {
+        if next.precedence() > self.precedence() {
+            next
+        } else {
+            self
+        }
+    }
 
-#[derive(Debug)]
+    /// Nothing later in the pipeline can override a block or a failure.
+    fn is_final(&self) -> bool {
+        matches!(self, PreRequestAction::Block(_) | PreRequestAction::Fail(_))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ProxyHttpPreRequest {
-    request: pre_request::HttpPreRequest,
-    mode: pre_request::ProxyMode,
+    pub(crate) request: pre_request::HttpPreRequest,
+    mode: ProxyMode,
+    block_status: StatusCode,
+    tunnel_to: Option<Hostname>,
 }
 
 impl pre_request::PreRequest for ProxyHttpPreRequest {
@@ -20,22 +63,78 @@ impl pre_request::PreRequest for ProxyHttpPreRequest {
         self.request.clone()
     }
 
-    fn http_set_proxy_mode(&mut self, mode: pre_request::ProxyMode) {
+    fn http_set_proxy_mode(&mut self, mode: ProxyMode) {
         self.mode = mode;
     }
+
+    fn http_block(&mut self, status: u16) -> Result<(), pre_request::Error> {
+        let status =
+            StatusCode::from_u16(status).map_err(|err| format!("Invalid status: {err}"))?;
+        self.block_status = status;
+        self.mode = ProxyMode::Block;
+        Ok(())
+    }
+
+    fn http_tunnel_to(&mut self, address: &str) -> Result<(), pre_request::Error> {
+        let hostname = Hostname::parse(address, &self.request.scheme)
+            .map_err(|err| format!("Invalid address: {err}"))?;
+        self.tunnel_to = Some(hostname);
+        self.mode = ProxyMode::TunnelTo;
+        Ok(())
+    }
 }
 
 impl ProxyHttpPreRequest {
-    pub fn new(hostname: Hostname) -> Self {
+    pub fn new(req: &Request<Body>, hostname: &Hostname, connection: &Connection) -> Self {
+        // CONNECT requests only carry an authority
+        let path = if req.method() == Method::CONNECT {
+            "/".to_string()
+        } else {
+            req.uri()
+                .path_and_query()
+                .map(|p_and_q| p_and_q.to_string())
+                .unwrap_or_else(|| "/".to_string())
+        };
+        let headers = req
+            .headers()
+            .iter()
+            .flat_map(|(name, value)| match value.to_str() {
+                Ok(value) => Some((name.to_string(), value.to_string())),
+                Err(_) => None,
+            })
+            .collect();
         let request = pre_request::HttpPreRequest {
-            path: "/".into(),
-            authority: hostname.authority,
-            host: hostname.host,
-            scheme: hostname.scheme,
+            path,
+            authority: hostname.authority.clone(),
+            host: hostname.host.clone(),
+            scheme: hostname.scheme.clone(),
+            method: req.method().to_string(),
+            version: format!("{:?}", req.version()),
+            headers,
+            client_address: connection.client_address.to_string(),
+            listener_name: connection.listener_name.clone(),
+            listener_port: connection.listener_port,
         };
         Self {
             request,
-            mode: pre_request::ProxyMode::Pass,
+            mode: ProxyMode::Pass,
+            block_status: StatusCode::FORBIDDEN,
+            tunnel_to: None,
+        }
+    }
+
+    pub fn action(self) -> PreRequestAction {
+        match self.mode {
+            ProxyMode::Intercept => PreRequestAction::Intercept,
+            ProxyMode::Pass => PreRequestAction::Pass,
+            ProxyMode::Block => PreRequestAction::Block(self.block_status),
+            ProxyMode::TunnelTo => match self.tunnel_to {
+                Some(hostname) => PreRequestAction::TunnelTo(hostname),
+                None => {
+                    tracing::warn!("Tunnel requested without an address, passing through.");
+                    PreRequestAction::Pass
+                }
+            },
         }
     }
 }
@@ -44,65 +143,137 @@ struct PreRequestContext {
     wasi: WasiCtx,
     proxy_request: ProxyHttpPreRequest,
     proxy_config: ProxyConfig,
+    services: HostServices,
+    limits: StoreLimits,
 }
 
+/// Asks each module of the pipeline, then the pre-request script, what to do with the request,
+/// combining their decisions with [`PreRequestAction::combine`]. The pipeline stops early once
+/// the request is blocked, a module fails closed, or a module stops the pipeline.
 pub async fn process_pre_request(
     wasi_runtime: &mut WasiRuntime,
-    hostname: Hostname,
-    wasi_module_path: Option<PathBuf>,
+    proxy_request: ProxyHttpPreRequest,
+    modules: Vec<WasiModule>,
     proxy: Proxy,
-) -> Result<pre_request::ProxyMode> {
-    let wasi_module_path = match wasi_module_path {
-        Some(wasi_module_path) => wasi_module_path,
-        None => {
-            return Ok(pre_request::ProxyMode::Pass);
-        }
-    };
-
-    tracing::trace!("Building request.");
-    let proxy_request = ProxyHttpPreRequest::new(hostname);
+    services: HostServices,
+) -> Result<PreRequestAction> {
     tracing::trace!(?proxy_request, "Built request.");
-    let module = wasi_runtime
-        .fetch_module(wasi_module_path.as_path())
+    let mut action = PreRequestAction::Pass;
+    let mut stopped = false;
+    for module in modules.iter() {
+        let (module_action, module_stopped) = run_pre_request_module(
+            wasi_runtime,
+            proxy_request.clone(),
+            module,
+            &proxy,
+            services.for_module(module),
+        )
         .await?;
+        tracing::debug!(
+            module = ?module.path,
+            action = ?module_action,
+            "Pre-request module decided."
+        );
+        action = action.combine(module_action);
+        stopped = module_stopped;
+        if stopped || action.is_final() {
+            break;
+        }
+    }
+
+    let script = proxy
+        .scripts
+        .pre_request
+        .as_ref()
+        .filter(|_script| !stopped && !action.is_final());
+    if let Some(script) = script {
+        let script_action = match run_pre_request_script(
+            wasi_runtime,
+            &services.scripts,
+            script,
+            &proxy.module_limits,
+            proxy_request,
+        )
+        .await
+        {
+            Ok(script_action) => script_action,
+            Err(err) => match proxy.module_limits.failure_policy {
+                FailurePolicy::Open => {
+                    tracing::warn!(
+                        ?err,
+                        script = ?script.path,
+                        "Pre-request script failed, passing the request through."
+                    );
+                    PreRequestAction::Pass
+                }
+                FailurePolicy::Closed => PreRequestAction::Fail(format!("{err:#}")),
+            },
+        };
+        tracing::debug!(
+            script = ?script.path,
+            action = ?script_action,
+            "Pre-request script decided."
+        );
+        action = action.combine(script_action);
+    }
+    Ok(action)
+}
 
-    let mut linker: Linker<PreRequestContext> = Linker::new(&wasi_runtime.engine);
-    let wasi = WasiCtxBuilder::new()
-        .inherit_stdio()
-        .inherit_args()?
-        .build();
+/// Runs a single stage of the pipeline, returning its decision and whether it stopped the
+/// pipeline.
+async fn run_pre_request_module(
+    wasi_runtime: &mut WasiRuntime,
+    proxy_request: ProxyHttpPreRequest,
+    module: &WasiModule,
+    proxy: &Proxy,
+    services: HostServices,
+) -> Result<(PreRequestAction, bool)> {
+    let limits = proxy.module_limits.clone();
+    let wasi = build_wasi_ctx(&proxy.capabilities.pre_request, &module.path)?;
     tracing::trace!("Built WASI context.");
     let ctx = PreRequestContext {
         wasi,
         proxy_request,
-        proxy_config: ProxyConfig {
-            proxy,
-            error: "".into(),
-        },
+        proxy_config: ProxyConfig::for_module(proxy, module),
+        services,
+        limits: WasiRuntime::store_limits(&limits),
     };
 
     let mut store: Store<PreRequestContext> = Store::new(&wasi_runtime.engine, ctx);
-    wasi_runtime::add_to_linker(&mut linker, |s| &mut s.wasi)?;
-    tracing::trace!("Linked module.");
-
-    pre_request::add_to_linker(&mut linker, |ctx| -> &mut ProxyHttpPreRequest {
-        &mut ctx.proxy_request
-    })?;
-    add_to_linker(&mut linker, |ctx| -> &mut ProxyConfig {
-        &mut ctx.proxy_config
-    })?;
+    store.limiter(|ctx| &mut ctx.limits);
+    wasi_runtime.apply_limits(&mut store, &limits)?;
+    let instance_pre = wasi_runtime
+        .instance_pre(&mut store, module.path.as_path(), |linker| {
+            wasi_runtime::add_to_linker(linker, |s: &mut PreRequestContext| &mut s.wasi)?;
+            pre_request::add_to_linker(linker, |ctx| -> &mut ProxyHttpPreRequest {
+                &mut ctx.proxy_request
+            })?;
+            add_to_linker(linker, |ctx| -> &mut ProxyConfig { &mut ctx.proxy_config })?;
+            HostServices::add_to_linker(linker, |ctx| -> &mut HostServices { &mut ctx.services })?;
+            Ok(())
+        })
+        .await?;
     tracing::trace!("Linked module with WIT.");
 
-    linker.module(&mut store, "", &module)?;
-    tracing::trace!("Added module to linker.");
-    linker
-        .get_default(&mut store, "")?
-        .typed::<(), (), _>(&store)?
-        .call(&mut store, ())?;
+    let (result, data) = wasi_runtime
+        .run_module(instance_pre, store, &limits)
+        .await?;
     tracing::trace!("Called WASI module.");
 
-    let data = store.into_data();
-    tracing::trace!("Fetched request context from store.");
-    let proxy_mode = data.proxy_request.mode;
-    Ok(proxy_mode)
+    if let Err(err) = result {
+        return match limits.failure_policy {
+            FailurePolicy::Open => {
+                tracing::warn!(
+                    ?err,
+                    module = ?module.path,
+                    "Pre-request module failed, passing the request through."
+                );
+                Ok((PreRequestAction::Pass, false))
+            }
+            FailurePolicy::Closed => Ok((PreRequestAction::Fail(format!("{err:#}")), true)),
+        };
+    }
+
+    let stopped = data.services.pipeline.stopped();
+    Ok((data.proxy_request.action(), stopped))
 }
@@ -2,22 +2,31 @@ use std::{convert::Infallible, path::Path, sync::Arc};
 
 use anyhow::Result;
 use ca::CertificateAuthority;
-use config::Proxy;
+use config::{HttpClientPolicy, Proxy};
 use http::{Request, Response, StatusCode, Uri, Version};
 use hyper::{client::HttpConnector, server::conn::Http, service::service_fn, Body};
 use hyper_alpn::AlpnConnector;
 use hyper_tls::HttpsConnector;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_rustls::TlsAcceptor;
-use wasi_runtime::WasiRuntime;
+use tracing::Instrument;
 
 use crate::tcp::tunnel;
 
 use super::{
+    client::ModuleHttpClient,
+    connection::Connection,
+    flow::Flow,
+    host::HostServices,
     hostname::Hostname,
-    pre_request::{process_pre_request, ProxyMode},
-    request::process_request,
-    response::process_response,
+    interceptor::{HttpInterceptor, PassThrough},
+    log::ModuleLog,
+    pipeline::PipelineControl,
+    pre_request::PreRequestAction,
+    request::RequestOutcome,
+    response::RequestLine,
+    script::ScriptCache,
+    state::StateStore,
 };
 
 // Each protocol defines a context, and is passed in via process request
@@ -27,6 +36,9 @@ pub struct HttpContext {
     client_h2: hyper::Client<AlpnConnector, hyper::Body>,
     #[allow(unused)]
     ca: CertificateAuthority,
+    state: StateStore,
+    scripts: ScriptCache,
+    flow: Flow,
 }
 
 impl HttpContext {
@@ -44,8 +56,42 @@ impl HttpContext {
             client_h1,
             client_h2,
             ca,
+            state: StateStore::default(),
+            scripts: ScriptCache::default(),
+            flow: Flow::default(),
         })
     }
+
+    /// Uses `state` as the state shared by the modules run through this context.
+    pub fn with_state(mut self, state: StateStore) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// The PEM encoded certificate of the CA intercepted HTTPS connections are signed by.
+    pub async fn ca_certificate(&self) -> Result<Vec<u8>> {
+        self.ca.certificate().await
+    }
+
+    /// A copy of the context for handling a new request.
+    fn for_flow(&self) -> Self {
+        Self {
+            flow: Flow::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Builds what a hook's modules can use besides the hook. Each module's own requests are
+    /// restricted by its policy, see `HostServices::for_module`.
+    pub(crate) fn host_services(&self) -> HostServices {
+        HostServices {
+            http_client: ModuleHttpClient::new(self.client_h1.clone(), HttpClientPolicy::default()),
+            state: self.state.clone(),
+            log: ModuleLog::new(self.flow.clone()),
+            pipeline: PipelineControl::default(),
+            scripts: self.scripts.clone(),
+        }
+    }
 }
 async fn negotiate_version(scheme: &str, host: &str, context: &HttpContext) -> Result<Version> {
     let uri = Uri::builder()
@@ -80,7 +126,7 @@ fn error_payload(error: anyhow::Error) -> Response<Body> {
 async fn http_proxy_service(
     mut req: Request<Body>,
     proxy: Proxy,
-    mut wasi_runtime: WasiRuntime,
+    interceptor: Arc<dyn HttpInterceptor>,
     context: HttpContext,
     version: Option<Version>,
 ) -> Result<Response<Body>, Infallible> {
@@ -95,34 +141,37 @@ async fn http_proxy_service(
         .map(|p_and_q| p_and_q.as_str())
         .unwrap_or("/");
     let host = proxy.upstream_address();
-    let req_path = proxy.request_wasi_module_path.clone();
-    let resp_path = proxy.response_wasi_module_path.clone();
     *req.uri_mut() = Uri::builder()
         .scheme(scheme.as_str())
         .authority(host.as_str())
         .path_and_query(p_and_q)
         .build()
         .unwrap();
-    let request = match process_request(
-        &mut wasi_runtime,
-        req,
-        req_path,
-        scheme.as_str(),
-        host.as_str(),
-        proxy.clone(),
-    )
-    .await
-    {
-        Ok(request) => {
-            tracing::info!(new_request = ?request, "New request.");
-            request
+    let outcome = match interceptor.on_request(req, &proxy, &context).await {
+        Ok(outcome) => {
+            tracing::info!(new_request = ?outcome, "New request.");
+            outcome
         }
         Err(err) => {
-            tracing::error!(?err, "Error getting request from WASM.");
+            tracing::error!(?err, "Error intercepting request.");
             return Ok(error_payload(err));
         }
     };
 
+    let request = match outcome {
+        RequestOutcome::Forward(request) => request,
+        // The module answered the request itself, so the upstream is never contacted
+        RequestOutcome::Respond(request, resp) => {
+            let request_line = RequestLine {
+                method: request.method().clone(),
+                uri: request.uri().clone(),
+                version: request.version(),
+            };
+            return finish_response(interceptor.as_ref(), resp, proxy, request_line, &context)
+                .await;
+        }
+    };
+
     let version = match version {
         Some(version) => version,
         None => match negotiate_version(&scheme, &host, &context).await {
@@ -134,8 +183,11 @@ async fn http_proxy_service(
         },
     };
 
-    let method = request.method().clone();
-    let uri = request.uri().clone();
+    let request_line = RequestLine {
+        method: request.method().clone(),
+        uri: request.uri().clone(),
+        version,
+    };
 
     let resp = match version {
         Version::HTTP_09 | Version::HTTP_10 | Version::HTTP_11 => context
@@ -162,23 +214,38 @@ async fn http_proxy_service(
         }
     };
 
-    match process_response(
-        &mut wasi_runtime,
-        resp,
-        resp_path,
-        proxy,
-        uri,
-        version,
-        method,
-    )
-    .await
+    finish_response(interceptor.as_ref(), resp, proxy, request_line, &context).await
+}
+
+async fn finish_response(
+    interceptor: &dyn HttpInterceptor,
+    resp: Response<Body>,
+    proxy: Proxy,
+    request_line: RequestLine,
+    context: &HttpContext,
+) -> Result<Response<Body>, Infallible> {
+    match interceptor
+        .on_response(resp, request_line, &proxy, context)
+        .await
     {
         Ok(resp) => {
-            tracing::info!(new_response = ?resp, "New response.");
+            let annotations = context.flow.annotations();
+            let changed_by: Vec<String> = context
+                .flow
+                .changes()
+                .iter()
+                .map(|change| format!("{} {}", change.hook, change.stage.display()))
+                .collect();
+            tracing::info!(
+                new_response = ?resp,
+                ?annotations,
+                ?changed_by,
+                "New response."
+            );
             Ok(resp)
         }
         Err(err) => {
-            tracing::error!(?err, "Error processing response from WASM.");
+            tracing::error!(?err, "Error intercepting response.");
             Ok(error_payload(err))
         }
     }
@@ -187,14 +254,16 @@ async fn http_proxy_service(
 pub async fn http_proxy<T: AsyncRead + AsyncWrite + std::marker::Unpin + 'static>(
     socket: T,
     proxy: Proxy,
-    wasi_runtime: WasiRuntime,
+    interceptor: Arc<dyn HttpInterceptor>,
     context: HttpContext,
 ) -> Result<()> {
     let service = service_fn(|request: Request<Body>| {
-        let wasi_runtime = wasi_runtime.clone();
-        let context = context.clone();
+        let interceptor = interceptor.clone();
+        let context = context.for_flow();
+        let span = context.flow.span();
         let proxy = proxy.clone();
-        async move { http_proxy_service(request, proxy, wasi_runtime, context, None).await }
+        async move { http_proxy_service(request, proxy, interceptor, context, None).await }
+            .instrument(span)
     });
 
     if let Err(http_err) = Http::new().serve_connection(socket, service).await {
@@ -207,18 +276,18 @@ pub async fn http_proxy<T: AsyncRead + AsyncWrite + std::marker::Unpin + 'static
 pub async fn https_proxy<T: AsyncRead + AsyncWrite + std::marker::Unpin + 'static>(
     socket: T,
     proxy: Proxy,
-    wasi_runtime: WasiRuntime,
+    interceptor: Arc<dyn HttpInterceptor>,
     mut context: HttpContext,
     hostname: Hostname,
 ) -> Result<()> {
-    let version = negotiate_version(&hostname.scheme, &hostname.host, &context).await?;
+    let version = negotiate_version(&hostname.scheme, &hostname.authority, &context).await?;
     let alpn_protocols: Vec<Vec<u8>> = match version {
         Version::HTTP_2 => vec!["h2".into(), "http/1.1".into()],
         _ => vec!["http/1.1".into()],
     };
     let mut config = context
         .ca
-        .build_certs(&hostname.host, hostname.port)
+        .build_certs(hostname.server_name(), hostname.port)
         .await?;
     config.alpn_protocols = alpn_protocols;
 
@@ -226,10 +295,12 @@ pub async fn https_proxy<T: AsyncRead + AsyncWrite + std::marker::Unpin + 'stati
     let stream = acceptor.accept(socket).await?;
 
     let service = service_fn(|request: Request<Body>| {
-        let wasi_runtime = wasi_runtime.clone();
-        let context = context.clone();
+        let interceptor = interceptor.clone();
+        let context = context.for_flow();
+        let span = context.flow.span();
         let proxy = proxy.clone();
-        async move { http_proxy_service(request, proxy, wasi_runtime, context, Some(version)).await }
+        async move { http_proxy_service(request, proxy, interceptor, context, Some(version)).await }
+            .instrument(span)
     });
 
     if let Err(http_err) = Http::new().serve_connection(stream, service).await {
@@ -239,77 +310,144 @@ pub async fn https_proxy<T: AsyncRead + AsyncWrite + std::marker::Unpin + 'stati
     Ok(())
 }
 
+fn blocked_payload(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = status;
+    resp
+}
+
+async fn pre_request_action(
+    req: &Request<Body>,
+    hostname: &Hostname,
+    connection: &Connection,
+    proxy: &Proxy,
+    interceptor: &dyn HttpInterceptor,
+    context: &HttpContext,
+) -> PreRequestAction {
+    match interceptor
+        .pre_request(req, hostname, connection, proxy, context)
+        .await
+    {
+        Ok(action) => action,
+        Err(err) => {
+            tracing::error!(?err, "Error deciding what to do with the request.");
+            PreRequestAction::Pass
+        }
+    }
+}
+
 async fn proxy_https(
     req: Request<Body>,
     hostname: Hostname,
+    connection: Connection,
     proxy: Proxy,
-    mut wasi_runtime: WasiRuntime,
+    interceptor: Arc<dyn HttpInterceptor>,
     context: HttpContext,
-) -> Result<(), Infallible> {
+) -> Result<Response<Body>, Infallible> {
+    let mut proxy = proxy.clone();
+    proxy.upstream_address = hostname.host.clone();
+    proxy.upstream_port = hostname.port;
+    proxy.tls = hostname.scheme == "https";
+    let action = pre_request_action(
+        &req,
+        &hostname,
+        &connection,
+        &proxy,
+        interceptor.as_ref(),
+        &context,
+    )
+    .await;
+
+    // Blocking has to happen before the tunnel is established
+    match action {
+        PreRequestAction::Block(status) => {
+            tracing::info!(%status, "Blocked tunnel.");
+            return Ok(blocked_payload(status));
+        }
+        PreRequestAction::Fail(message) => {
+            return Ok(error_payload(anyhow::Error::msg(message)));
+        }
+        _ => {}
+    }
+
     tokio::spawn(async move {
         match hyper::upgrade::on(req).await {
-            Ok(upgraded) => {
-                let path = proxy.pre_request_wasi_module_path.clone();
-                let mut proxy = proxy.clone();
-                proxy.upstream_address = hostname.host.clone();
-                proxy.upstream_port = hostname.port;
-                proxy.tls = false;
-                match process_pre_request(&mut wasi_runtime, hostname.clone(), path, proxy.clone())
-                    .await
-                    .unwrap_or(ProxyMode::Pass)
-                {
-                    ProxyMode::Intercept => {
-                        let res =
-                            https_proxy(upgraded, proxy, wasi_runtime, context, hostname).await;
-                        tracing::info!(?res, "Finished intercepting.");
-                    }
-                    ProxyMode::Pass => {
-                        let res = tunnel(upgraded, &hostname.authority).await;
-                        tracing::info!(?res, "Finished tunneling.");
-                    }
+            Ok(upgraded) => match action {
+                PreRequestAction::Intercept if !proxy.tls => {
+                    let res = http_proxy(upgraded, proxy, interceptor, context).await;
+                    tracing::info!(?res, "Finished intercepting.");
                 }
-            }
+                PreRequestAction::Intercept => {
+                    let res = https_proxy(upgraded, proxy, interceptor, context, hostname).await;
+                    tracing::info!(?res, "Finished intercepting.");
+                }
+                PreRequestAction::TunnelTo(destination) => {
+                    let res = tunnel(upgraded, &destination.authority).await;
+                    tracing::info!(?res, destination = %destination.authority, "Finished tunneling.");
+                }
+                PreRequestAction::Pass | PreRequestAction::Block(_) | PreRequestAction::Fail(_) => {
+                    let res = tunnel(upgraded, &hostname.authority).await;
+                    tracing::info!(?res, "Finished tunneling.");
+                }
+            },
             Err(err) => tracing::error!(%err, "Error upgrading request."),
         };
     });
-    Ok(())
+    Ok(Response::new(Body::empty()))
 }
 
 async fn proxy_http(
     req: Request<Body>,
     hostname: Hostname,
+    connection: Connection,
     proxy: Proxy,
-    mut wasi_runtime: WasiRuntime,
+    interceptor: Arc<dyn HttpInterceptor>,
     context: HttpContext,
 ) -> Result<Response<Body>, Infallible> {
-    let path = proxy.pre_request_wasi_module_path.clone();
     let mut proxy = proxy.clone();
     proxy.upstream_address = hostname.host.clone();
     proxy.upstream_port = hostname.port;
-    proxy.tls = false;
-    match process_pre_request(&mut wasi_runtime, hostname.clone(), path, proxy.clone())
-        .await
-        .unwrap_or(ProxyMode::Pass)
+    proxy.tls = hostname.scheme == "https";
+    match pre_request_action(
+        &req,
+        &hostname,
+        &connection,
+        &proxy,
+        interceptor.as_ref(),
+        &context,
+    )
+    .await
     {
-        ProxyMode::Intercept => {
-            let res = http_proxy_service(req, proxy, wasi_runtime, context, None).await;
+        PreRequestAction::Intercept => {
+            let res = http_proxy_service(req, proxy, interceptor, context, None).await;
             tracing::info!(?res, "Finished intercepting.");
             res
         }
-        ProxyMode::Pass => {
-            proxy.request_wasi_module_path = None;
-            proxy.response_wasi_module_path = None;
-            let res = http_proxy_service(req, proxy, wasi_runtime, context, None).await;
+        PreRequestAction::Pass => {
+            let res = http_proxy_service(req, proxy, Arc::new(PassThrough), context, None).await;
             tracing::info!(?res, "Finished tunneling.");
             res
         }
+        PreRequestAction::Block(status) => {
+            tracing::info!(%status, "Blocked request.");
+            Ok(blocked_payload(status))
+        }
+        PreRequestAction::Fail(message) => Ok(error_payload(anyhow::Error::msg(message))),
+        PreRequestAction::TunnelTo(destination) => {
+            proxy.upstream_address = destination.host.clone();
+            proxy.upstream_port = destination.port;
+            let res = http_proxy_service(req, proxy, Arc::new(PassThrough), context, None).await;
+            tracing::info!(?res, destination = %destination.authority, "Finished tunneling.");
+            res
+        }
     }
 }
 
 async fn http_forward_proxy_service(
     req: Request<Body>,
+    connection: Connection,
     proxy: Proxy,
-    wasi_runtime: WasiRuntime,
+    interceptor: Arc<dyn HttpInterceptor>,
     context: HttpContext,
 ) -> Result<Response<Body>, Infallible> {
     tracing::info!(?req, "Received request");
@@ -322,11 +460,11 @@ async fn http_forward_proxy_service(
     };
 
     if req.method() == hyper::Method::CONNECT {
-        let res = proxy_https(req, hostname, proxy, wasi_runtime, context).await;
+        let res = proxy_https(req, hostname, connection, proxy, interceptor, context).await;
         tracing::info!(?res, "HTTPS proxy result.");
-        Ok(Response::new(hyper::Body::empty()))
+        res
     } else {
-        let res = proxy_http(req, hostname, proxy, wasi_runtime, context).await;
+        let res = proxy_http(req, hostname, connection, proxy, interceptor, context).await;
         tracing::info!(?res, "HTTP proxy result.");
         res
     }
@@ -335,14 +473,20 @@ async fn http_forward_proxy_service(
 pub async fn http_forward(
     socket: tokio::net::TcpStream,
     proxy: Proxy,
-    wasi_runtime: WasiRuntime,
+    interceptor: Arc<dyn HttpInterceptor>,
     context: HttpContext,
 ) -> Result<()> {
+    let connection = Connection::new(&socket, &proxy)?;
     let service = service_fn(|request: Request<Body>| {
-        let wasi_runtime = wasi_runtime.clone();
-        let context = context.clone();
+        let interceptor = interceptor.clone();
+        let context = context.for_flow();
+        let span = context.flow.span();
         let proxy = proxy.clone();
-        async move { http_forward_proxy_service(request, proxy, wasi_runtime, context).await }
+        let connection = connection.clone();
+        async move {
+            http_forward_proxy_service(request, connection, proxy, interceptor, context).await
+        }
+        .instrument(span)
     });
 
     if let Err(http_err) = Http::new()
@@ -358,14 +502,81 @@ pub async fn http_forward(
 
 #[cfg(test)]
 mod test {
-    use std::path::PathBuf;
+    use std::{path::PathBuf, time::Duration};
 
-    use crate::http::proxy::process_response;
+    use crate::http::{
+        pre_request::{process_pre_request, ProxyHttpPreRequest},
+        request::process_request,
+        response::process_response,
+    };
 
-    use super::{process_request, WasiRuntime};
-    use config::Proxy;
-    use http::{Response, Uri};
+    use super::{
+        Connection, HostServices, Hostname, PreRequestAction, RequestLine, RequestOutcome,
+    };
+    use config::{FailurePolicy, HttpClientPolicy, ModuleLimits, Proxy, WasiModule};
+    use http::{Response, StatusCode, Uri};
     use hyper::{Body, Request};
+    use tempfile::TempDir;
+    use wasi_runtime::WasiRuntime;
+
+    fn module(path: PathBuf) -> WasiModule {
+        WasiModule {
+            path,
+            configuration_path: None,
+            configuration_bytes: None,
+            http_client: HttpClientPolicy::default(),
+        }
+    }
+
+    async fn pre_request_action(uri: &str) -> PreRequestAction {
+        let request = Request::builder()
+            .method("GET")
+            .uri(uri)
+            .body(Body::empty())
+            .expect("should build the request");
+        let hostname = Hostname::try_from(&request).expect("should parse the hostname");
+        let connection = Connection {
+            client_address: "127.0.0.1:52000".parse().expect("should parse the address"),
+            listener_name: "test".into(),
+            listener_port: 9999,
+        };
+
+        let mut wasi_path = std::env::current_dir().expect("should get the current directory");
+        wasi_path.push(
+            "../wit-bindings/tests/http-pre-request/target/wasm32-wasi/debug/http-pre-request.wasm",
+        );
+        let mut wasi_runtime =
+            WasiRuntime::new(PathBuf::from("/")).expect("should build the runtime");
+        process_pre_request(
+            &mut wasi_runtime,
+            ProxyHttpPreRequest::new(&request, &hostname, &connection),
+            vec![module(wasi_path)],
+            Proxy::new(),
+            HostServices::default(),
+        )
+        .await
+        .expect("should process the pre-request")
+    }
+
+    #[tokio::test]
+    async fn processes_pre_request() {
+        let action = pre_request_action("http://localhost:8000/").await;
+        assert!(matches!(action, PreRequestAction::Intercept));
+
+        let action = pre_request_action("http://example.com/blocked").await;
+        assert!(matches!(
+            action,
+            PreRequestAction::Block(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS)
+        ));
+
+        let action = pre_request_action("http://old.example.com/").await;
+        match action {
+            PreRequestAction::TunnelTo(destination) => {
+                assert_eq!(destination.authority, "new.example.com:8080")
+            }
+            action => panic!("unexpected action: {action:?}"),
+        }
+    }
 
     #[tokio::test]
     async fn processes_request() {
@@ -379,17 +590,22 @@ mod test {
             .push("../wit-bindings/tests/http-request/target/wasm32-wasi/debug/http-request.wasm");
         let mut wasi_runtime =
             WasiRuntime::new(PathBuf::from("/")).expect("should build the runtime");
-        let new_request = process_request(
+        let outcome = process_request(
             &mut wasi_runtime,
             request,
-            Some(wasi_path),
+            vec![module(wasi_path)],
             "http",
             "localhost",
             Proxy::new(),
+            HostServices::default(),
         )
         .await
         .expect("should process the request");
 
+        let new_request = match outcome {
+            RequestOutcome::Forward(new_request) => new_request,
+            outcome => panic!("unexpected outcome: {outcome:?}"),
+        };
         let (parts, body) = new_request.into_parts();
         assert_eq!(parts.method, "post");
         let body = hyper::body::to_bytes(body)
@@ -399,6 +615,225 @@ mod test {
         assert_eq!(body_str, "haha!");
     }
 
+    #[tokio::test]
+    async fn responds_to_request() {
+        let request = Request::builder()
+            .method("get")
+            .uri("/mock")
+            .body(Body::empty())
+            .expect("should build the request");
+
+        let mut wasi_path = std::env::current_dir().expect("should get the current directory");
+        wasi_path
+            .push("../wit-bindings/tests/http-request/target/wasm32-wasi/debug/http-request.wasm");
+        let mut wasi_runtime =
+            WasiRuntime::new(PathBuf::from("/")).expect("should build the runtime");
+        let outcome = process_request(
+            &mut wasi_runtime,
+            request,
+            vec![module(wasi_path)],
+            "http",
+            "localhost",
+            Proxy::new(),
+            HostServices::default(),
+        )
+        .await
+        .expect("should process the request");
+
+        let response = match outcome {
+            RequestOutcome::Respond(_, response) => response,
+            outcome => panic!("unexpected outcome: {outcome:?}"),
+        };
+        let (parts, body) = response.into_parts();
+        assert_eq!(parts.status, StatusCode::IM_A_TEAPOT);
+        assert_eq!(parts.headers["content-type"], "text/plain");
+        let body = hyper::body::to_bytes(body)
+            .await
+            .expect("should read the body");
+        assert_eq!(&body[..], b"mocked!");
+    }
+
+    #[tokio::test]
+    async fn shares_state_across_requests() {
+        let mut wasi_path = std::env::current_dir().expect("should get the current directory");
+        wasi_path
+            .push("../wit-bindings/tests/http-request/target/wasm32-wasi/debug/http-request.wasm");
+        let mut wasi_runtime =
+            WasiRuntime::new(PathBuf::from("/")).expect("should build the runtime");
+        let services = HostServices::default();
+
+        for expected in ["1", "2"] {
+            let request = Request::builder()
+                .method("get")
+                .uri("/count")
+                .body(Body::empty())
+                .expect("should build the request");
+            let outcome = process_request(
+                &mut wasi_runtime,
+                request,
+                vec![module(wasi_path.clone())],
+                "http",
+                "localhost",
+                Proxy::new(),
+                services.clone(),
+            )
+            .await
+            .expect("should process the request");
+            let response = match outcome {
+                RequestOutcome::Respond(_, response) => response,
+                outcome => panic!("unexpected outcome: {outcome:?}"),
+            };
+            let body = hyper::body::to_bytes(response.into_body())
+                .await
+                .expect("should read the body");
+            assert_eq!(&body[..], expected.as_bytes());
+        }
+        assert_eq!(services.state.get("count"), Some(b"2".to_vec()));
+    }
+
+    /// Writes a module whose entry point never returns.
+    fn looping_module(dir: &TempDir) -> PathBuf {
+        let path = dir.path().join("looping-module.wat");
+        std::fs::write(&path, r#"(module (func (export "_start") (loop (br 0))))"#)
+            .expect("should write the module");
+        path
+    }
+
+    async fn limited_request(limits: ModuleLimits) -> anyhow::Result<RequestOutcome> {
+        let request = Request::builder()
+            .method("get")
+            .uri("/")
+            .body(Body::from("hello"))
+            .expect("should build the request");
+        let mut proxy = Proxy::new();
+        proxy.module_limits = limits;
+        let mut wasi_runtime =
+            WasiRuntime::new(PathBuf::from("/")).expect("should build the runtime");
+        let dir = tempfile::Builder::new()
+            .prefix("proxysaur-limits")
+            .tempdir()
+            .expect("should create a temp dir");
+        process_request(
+            &mut wasi_runtime,
+            request,
+            vec![module(looping_module(&dir))],
+            "http",
+            "localhost",
+            proxy,
+            HostServices::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn stops_the_pipeline() {
+        let dir = tempfile::Builder::new()
+            .prefix("proxysaur-pipeline")
+            .tempdir()
+            .expect("should create a temp dir");
+        let stopping_module = dir.path().join("stopping-module.wat");
+        std::fs::write(
+            &stopping_module,
+            r#"(module
+                (import "pipeline" "pipeline-stop" (func $stop))
+                (func (export "_start") (call $stop)))"#,
+        )
+        .expect("should write the module");
+        let request = Request::builder()
+            .method("get")
+            .uri("/")
+            .body(Body::from("hello"))
+            .expect("should build the request");
+        let mut proxy = Proxy::new();
+        proxy.module_limits = ModuleLimits {
+            timeout_ms: Some(50),
+            ..ModuleLimits::default()
+        };
+        let mut wasi_runtime =
+            WasiRuntime::new(PathBuf::from("/")).expect("should build the runtime");
+        // The looping module would time out if it ran
+        let outcome = process_request(
+            &mut wasi_runtime,
+            request,
+            vec![module(stopping_module), module(looping_module(&dir))],
+            "http",
+            "localhost",
+            proxy,
+            HostServices::default(),
+        )
+        .await
+        .expect("should skip the rest of the pipeline");
+        assert!(matches!(outcome, RequestOutcome::Forward(_)));
+    }
+
+    #[test]
+    fn combines_pre_request_actions() {
+        let hostname = Hostname::parse("new.example.com:8080", "http").expect("should parse");
+        let action = PreRequestAction::Pass
+            .combine(PreRequestAction::Intercept)
+            .combine(PreRequestAction::TunnelTo(hostname))
+            .combine(PreRequestAction::Intercept);
+        assert!(matches!(action, PreRequestAction::TunnelTo(_)));
+
+        let action = action.combine(PreRequestAction::Block(StatusCode::FORBIDDEN));
+        assert!(matches!(
+            action,
+            PreRequestAction::Block(StatusCode::FORBIDDEN)
+        ));
+        let action = action.combine(PreRequestAction::Block(StatusCode::NOT_FOUND));
+        assert!(matches!(
+            action,
+            PreRequestAction::Block(StatusCode::FORBIDDEN)
+        ));
+    }
+
+    #[tokio::test]
+    async fn times_out_and_fails_closed() {
+        let limits = ModuleLimits {
+            timeout_ms: Some(50),
+            ..ModuleLimits::default()
+        };
+        let err = limited_request(limits)
+            .await
+            .expect_err("should stop the module");
+        assert!(format!("{err:?}").contains("interrupt"), "{err:?}");
+    }
+
+    #[tokio::test]
+    async fn modules_do_not_block_the_executor() {
+        let limits = ModuleLimits {
+            timeout_ms: Some(500),
+            ..ModuleLimits::default()
+        };
+        // Tests run on a single threaded executor, so the timer can only fire if the module
+        // runs somewhere else
+        tokio::select! {
+            _ = limited_request(limits) => panic!("the module should still be running"),
+            _ = tokio::time::sleep(Duration::from_millis(50)) => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_out_of_fuel_and_fails_open() {
+        let limits = ModuleLimits {
+            fuel: Some(10_000),
+            failure_policy: FailurePolicy::Open,
+            ..ModuleLimits::default()
+        };
+        let outcome = limited_request(limits)
+            .await
+            .expect("should pass the request through");
+        let request = match outcome {
+            RequestOutcome::Forward(request) => request,
+            outcome => panic!("unexpected outcome: {outcome:?}"),
+        };
+        assert_eq!(request.method(), "get");
+        let body = hyper::body::to_bytes(request.into_body())
+            .await
+            .expect("should read the body");
+        assert_eq!(&body[..], b"hello");
+    }
+
     #[tokio::test]
     async fn processes_response() {
         let response = Response::builder()
@@ -424,18 +859,19 @@ mod test {
             .method("HEAD")
             .body(Body::from(""))
             .expect("");
-        let uri = req.uri().clone();
-        let version = req.version();
-        let method = req.method().clone();
+        let request_line = RequestLine {
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            version: req.version(),
+        };
 
         let new_response: Response<Body> = process_response(
             &mut wasi_runtime,
             response,
-            Some(wasi_path),
+            vec![module(wasi_path)],
             proxy,
-            uri,
-            version,
-            method,
+            request_line,
+            HostServices::default(),
         )
         .await
         .expect("should process the response");
@@ -1,20 +1,37 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use anyhow::Result;
-use config::Proxy;
-use http::Uri;
-use hyper::{Body, Request};
+use config::{FailurePolicy, Proxy, WasiModule};
+use http::{
+    header::{HeaderName, HeaderValue},
+    StatusCode, Uri,
+};
+use hyper::{Body, Request, Response};
 use proxysaur_wit_bindings::config::config::add_to_linker;
 use proxysaur_wit_bindings::http::request;
-use wasi_runtime::{Linker, Store, WasiCtx, WasiCtxBuilder, WasiRuntime};
+use wasi_runtime::{build_wasi_ctx, Store, StoreLimits, WasiCtx, WasiRuntime};
 
 use crate::http::convert_version;
 
-use super::{config::ProxyConfig, ProxyHttpError};
+use super::{
+    config::ProxyConfig,
+    diff::{HookDiff, Snapshot},
+    host::HostServices,
+    script::run_request_script,
+    ProxyHttpError,
+};
 
 #[derive(Debug)]
 pub struct ProxyHttpRequest {
-    request: request::HttpRequestResult,
+    pub(crate) request: request::HttpRequestResult,
+    pub(crate) response: Option<Response<Body>>,
+}
+
+/// A request module either modifies the request sent upstream, or answers it directly.
+#[derive(Debug)]
+pub enum RequestOutcome {
+    Forward(Request<Body>),
+    Respond(Request<Body>, Response<Body>),
 }
 
 impl TryFrom<ProxyHttpRequest> for Request<Body> {
@@ -30,12 +47,14 @@ impl TryFrom<ProxyHttpRequest> for Request<Body> {
             .path_and_query(request.path)
             .build()?;
         tracing::info!(?uri, "Built URI.");
-        let request = Request::builder()
+        let mut builder = Request::builder()
             .method(request.method.as_str())
             .version(convert_version(&request.version)?)
-            .uri(uri)
-            .body(body)
-            .map_err(ProxyHttpError::from)?;
+            .uri(uri);
+        for (name, value) in request.headers.iter() {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let request = builder.body(body).map_err(ProxyHttpError::from)?;
         tracing::info!(?request, "Built request.");
 
         Ok(request)
@@ -55,7 +74,10 @@ impl ProxyHttpRequest {
             .authority()
             .map(|auth| auth.to_string())
             .unwrap_or_else(|| String::from(authority));
-        let path = uri.path().to_string();
+        let path = uri
+            .path_and_query()
+            .map(|path_and_query| path_and_query.to_string())
+            .unwrap_or_else(|| uri.path().to_string());
         let scheme = uri
             .scheme()
             .map(|scheme| scheme.to_string())
@@ -81,7 +103,17 @@ impl ProxyHttpRequest {
             host,
             body,
         };
-        Ok(Self { request })
+        Ok(Self {
+            request,
+            response: None,
+        })
+    }
+
+    pub(crate) fn into_outcome(mut self) -> Result<RequestOutcome, ProxyHttpError> {
+        match self.response.take() {
+            Some(response) => Ok(RequestOutcome::Respond(Request::try_from(self)?, response)),
+            None => Ok(RequestOutcome::Forward(Request::try_from(self)?)),
+        }
     }
 }
 
@@ -121,7 +153,10 @@ impl request::Request for ProxyHttpRequest {
             .authority()
             .map(|auth| auth.to_string())
             .unwrap_or_else(|| String::from(""));
-        self.request.path = uri.path().to_string();
+        self.request.path = uri
+            .path_and_query()
+            .map(|path_and_query| path_and_query.to_string())
+            .unwrap_or_else(|| uri.path().to_string());
         self.request.scheme = uri
             .scheme()
             .map(|scheme| scheme.to_string())
@@ -178,74 +213,238 @@ impl request::Request for ProxyHttpRequest {
             headers,
         };
     }
+
+    fn http_request_respond(
+        &mut self,
+        response: request::HttpReplyParam<'_>,
+    ) -> Result<(), request::Error> {
+        let status = StatusCode::from_u16(response.status)
+            .map_err(|err| format!("Invalid status: {err}"))?;
+        let mut builder = Response::builder().status(status);
+        for (name, value) in response.headers {
+            let name =
+                HeaderName::try_from(name).map_err(|err| format!("Invalid header: {err}"))?;
+            let value =
+                HeaderValue::try_from(value).map_err(|err| format!("Invalid header: {err}"))?;
+            builder = builder.header(name, value);
+        }
+        let response = builder
+            .body(Body::from(response.body.to_vec()))
+            .map_err(|err| format!("Invalid response: {err}"))?;
+        self.response = Some(response);
+        Ok(())
+    }
 }
 
 struct RequestContext {
     wasi: WasiCtx,
     proxy_request: ProxyHttpRequest,
     proxy_config: ProxyConfig,
+    services: HostServices,
+    limits: StoreLimits,
 }
 
+/// Runs the request through each module of the pipeline in turn, then through the request
+/// script, stopping early once a module answers the request or stops the pipeline.
 pub async fn process_request(
     wasi_runtime: &mut WasiRuntime,
     req: Request<Body>,
-    wasi_module_path: Option<PathBuf>,
+    modules: Vec<WasiModule>,
     scheme: &str,
     host: &str,
     proxy: Proxy,
-) -> Result<Request<Body>> {
-    let wasi_module_path = match wasi_module_path {
-        Some(wasi_module_path) => wasi_module_path,
-        None => {
-            return Ok(req);
-        }
-    };
+    services: HostServices,
+) -> Result<RequestOutcome> {
+    if modules.is_empty() && proxy.scripts.request.is_none() {
+        return Ok(RequestOutcome::Forward(req));
+    }
 
     tracing::trace!("Building request.");
-    let proxy_request = ProxyHttpRequest::new(req, scheme, host).await?;
+    let mut proxy_request = ProxyHttpRequest::new(req, scheme, host).await?;
     tracing::trace!(?proxy_request, "Built request.");
-    let module = wasi_runtime
-        .fetch_module(wasi_module_path.as_path())
+    let mut stopped = false;
+    for module in modules.iter() {
+        let before = proxy
+            .diff_hooks
+            .then(|| Snapshot::request(&proxy_request.request));
+        let (next_request, module_stopped) = run_request_module(
+            wasi_runtime,
+            proxy_request,
+            module,
+            &proxy,
+            services.for_module(module),
+        )
         .await?;
+        proxy_request = next_request;
+        record_change(&services, &module.path, before, &proxy_request);
+        stopped = module_stopped;
+        if stopped || proxy_request.response.is_some() {
+            break;
+        }
+    }
 
-    let mut linker: Linker<RequestContext> = Linker::new(&wasi_runtime.engine);
-    let wasi = WasiCtxBuilder::new()
-        .inherit_stdio()
-        .inherit_args()?
-        .build();
+    let script = proxy
+        .scripts
+        .request
+        .as_ref()
+        .filter(|_script| !stopped && proxy_request.response.is_none());
+    if let Some(script) = script {
+        let before = proxy
+            .diff_hooks
+            .then(|| Snapshot::request(&proxy_request.request));
+        let original_request = proxy_request.request.clone();
+        proxy_request = match run_request_script(
+            wasi_runtime,
+            &services.scripts,
+            script,
+            &proxy.module_limits,
+            proxy_request,
+        )
+        .await
+        {
+            Ok(proxy_request) => proxy_request,
+            Err(err) => match proxy.module_limits.failure_policy {
+                FailurePolicy::Open => {
+                    tracing::warn!(
+                        ?err,
+                        script = ?script.path,
+                        "Request script failed, passing the request through."
+                    );
+                    ProxyHttpRequest {
+                        request: original_request,
+                        response: None,
+                    }
+                }
+                FailurePolicy::Closed => return Err(err),
+            },
+        };
+        record_change(&services, &script.path, before, &proxy_request);
+    }
+
+    let outcome = proxy_request.into_outcome()?;
+    tracing::trace!(?outcome, "Built new request.");
+    Ok(outcome)
+}
+
+/// Records what a stage changed about the request, when the proxy diffs its hooks.
+fn record_change(
+    services: &HostServices,
+    stage: &Path,
+    before: Option<Snapshot>,
+    proxy_request: &ProxyHttpRequest,
+) {
+    if let Some(before) = before {
+        let after = Snapshot::request(&proxy_request.request);
+        let mut change = HookDiff::between("request", stage, &before, &after);
+        change.responded = proxy_request
+            .response
+            .as_ref()
+            .map(|response| response.status().as_u16());
+        services.log.record_change(change);
+    }
+}
+
+/// Runs a single stage of the pipeline, returning the request it leaves for the next stage and
+/// whether it stopped the pipeline.
+async fn run_request_module(
+    wasi_runtime: &mut WasiRuntime,
+    proxy_request: ProxyHttpRequest,
+    module: &WasiModule,
+    proxy: &Proxy,
+    services: HostServices,
+) -> Result<(ProxyHttpRequest, bool)> {
+    let original_request = proxy_request.request.clone();
+    let limits = proxy.module_limits.clone();
+    let wasi = build_wasi_ctx(&proxy.capabilities.request, &module.path)?;
     tracing::trace!("Built WASI context.");
     let ctx = RequestContext {
         wasi,
         proxy_request,
-        proxy_config: ProxyConfig {
-            proxy,
-            error: "".into(),
-        },
+        proxy_config: ProxyConfig::for_module(proxy, module),
+        services,
+        limits: WasiRuntime::store_limits(&limits),
     };
 
     let mut store: Store<RequestContext> = Store::new(&wasi_runtime.engine, ctx);
-    wasi_runtime::add_to_linker(&mut linker, |s| &mut s.wasi)?;
-    tracing::trace!("Linked module.");
-
-    request::add_to_linker(&mut linker, |ctx| -> &mut ProxyHttpRequest {
-        &mut ctx.proxy_request
-    })?;
-    add_to_linker(&mut linker, |ctx| -> &mut ProxyConfig {
-        &mut ctx.proxy_config
-    })?;
+    store.limiter(|ctx| &mut ctx.limits);
+    wasi_runtime.apply_limits(&mut store, &limits)?;
+    let instance_pre = wasi_runtime
+        .instance_pre(&mut store, module.path.as_path(), |linker| {
+            wasi_runtime::add_to_linker(linker, |s: &mut RequestContext| &mut s.wasi)?;
+            request::add_to_linker(linker, |ctx| -> &mut ProxyHttpRequest {
+                &mut ctx.proxy_request
+            })?;
+            add_to_linker(linker, |ctx| -> &mut ProxyConfig { &mut ctx.proxy_config })?;
+            HostServices::add_to_linker(linker, |ctx| -> &mut HostServices { &mut ctx.services })?;
+            Ok(())
+        })
+        .await?;
     tracing::trace!("Linked module with WIT.");
 
-    linker.module(&mut store, "", &module)?;
-    tracing::trace!("Added module to linker.");
-    linker
-        .get_default(&mut store, "")?
-        .typed::<(), (), _>(&store)?
-        .call(&mut store, ())?;
-    tracing::trace!("Called WASI module.");
-
-    let data = store.into_data();
-    tracing::trace!("Fetched request context from store.");
-    let new_request: Request<Body> = Request::try_from(data.proxy_request)?;
-    tracing::trace!(?new_request, "Built new request.");
-    Ok(new_request)
+    let (result, data) = wasi_runtime
+        .run_module(instance_pre, store, &limits)
+        .await?;
+    tracing::trace!(module = ?module.path, "Called WASI module.");
+
+    if let Err(err) = result {
+        return match limits.failure_policy {
+            FailurePolicy::Open => {
+                tracing::warn!(
+                    ?err,
+                    module = ?module.path,
+                    "Request module failed, passing the request through."
+                );
+                let original = ProxyHttpRequest {
+                    request: original_request,
+                    response: None,
+                };
+                Ok((original, false))
+            }
+            FailurePolicy::Closed => Err(err),
+        };
+    }
+
+    Ok((data.proxy_request, data.services.pipeline.stopped()))
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{Body, Request};
+
+    use super::ProxyHttpRequest;
+
+    #[tokio::test]
+    async fn keeps_the_query_in_the_path() {
+        let req = Request::builder()
+            .uri("http://localhost:8080/search?q=dino")
+            .body(Body::empty())
+            .expect("should build the request");
+        let proxy_request = ProxyHttpRequest::new(req, "http", "localhost:8080")
+            .await
+            .expect("should build the request");
+        assert_eq!(proxy_request.request.path, "/search?q=dino");
+    }
+
+    #[tokio::test]
+    async fn forwards_headers() {
+        let req = Request::builder()
+            .uri("http://localhost:8080/")
+            .header("accept", "text/html")
+            .header("set-cookie", "a=1")
+            .header("set-cookie", "b=2")
+            .body(Body::empty())
+            .expect("should build the request");
+        let mut proxy_request = ProxyHttpRequest::new(req, "http", "localhost:8080")
+            .await
+            .expect("should build the request");
+        proxy_request
+            .request
+            .headers
+            .push(("x-module".into(), "set".into()));
+        let req = Request::try_from(proxy_request).expect("should build the request");
+        assert_eq!(req.headers()["accept"], "text/html");
+        assert_eq!(req.headers()["x-module"], "set");
+        let cookies: Vec<_> = req.headers().get_all("set-cookie").iter().collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+    }
 }
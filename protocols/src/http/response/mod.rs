@@ -1,26 +1,43 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use anyhow::Result;
-use config::Proxy;
+use config::{FailurePolicy, Proxy, WasiModule};
 use http::{Method, Uri, Version};
 use hyper::{Body, Response};
 use proxysaur_wit_bindings::config::config::add_to_linker;
 use proxysaur_wit_bindings::http::response;
-use wasi_runtime::{Linker, Store, WasiCtx, WasiCtxBuilder, WasiRuntime};
+use wasi_runtime::{build_wasi_ctx, Store, StoreLimits, WasiCtx, WasiRuntime};
 
-use super::{config::ProxyConfig, ProxyHttpError};
+use super::{
+    config::ProxyConfig,
+    diff::{HookDiff, Snapshot},
+    host::HostServices,
+    script::run_response_script,
+    ProxyHttpError,
+};
 
+/// The request a response answers, as shown to response modules.
+#[derive(Debug, Clone)]
+pub struct RequestLine {
+    pub method: Method,
+    pub uri: Uri,
+    pub version: Version,
+}
+
+#[derive(Clone)]
 pub struct ProxyHttpResponse {
-    response: response::HttpResponse,
+    pub(crate) response: response::HttpResponse,
 }
 
 impl TryFrom<ProxyHttpResponse> for Response<Body> {
     type Error = ProxyHttpError;
 
     fn try_from(value: ProxyHttpResponse) -> Result<Self, Self::Error> {
-        let resp = Response::builder()
-            .status(value.response.status)
-            .body(Body::from(value.response.body))?;
+        let mut builder = Response::builder().status(value.response.status);
+        for (name, value) in value.response.headers.iter() {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let resp = builder.body(Body::from(value.response.body))?;
         Ok(resp)
     }
 }
@@ -28,10 +45,13 @@ impl TryFrom<ProxyHttpResponse> for Response<Body> {
 impl ProxyHttpResponse {
     pub async fn new(
         response: Response<Body>,
-        uri: Uri,
-        version: Version,
-        method: Method,
+        request_line: RequestLine,
     ) -> Result<Self, ProxyHttpError> {
+        let RequestLine {
+            method,
+            uri,
+            version,
+        } = request_line;
         let (parts, body) = response.into_parts();
         let headers = parts
             .headers
@@ -100,58 +120,150 @@ struct ResponseContext {
     wasi: WasiCtx,
     proxy_response: ProxyHttpResponse,
     config: ProxyConfig,
+    services: HostServices,
+    limits: StoreLimits,
 }
 
+/// Runs the response through each module of the pipeline in turn, then through the response
+/// script, stopping early if a module stops the pipeline.
 pub async fn process_response(
     wasi_runtime: &mut WasiRuntime,
     resp: Response<Body>,
-    wasi_module_path: Option<PathBuf>,
+    modules: Vec<WasiModule>,
     proxy: Proxy,
-    uri: Uri,
-    version: Version,
-    method: Method,
+    request_line: RequestLine,
+    services: HostServices,
 ) -> Result<Response<Body>> {
-    let wasi_module_path = match wasi_module_path {
-        Some(path) => path,
-        None => {
-            return Ok(resp);
-        }
-    };
-    let proxy_response = ProxyHttpResponse::new(resp, uri, version, method).await?;
-    let module = wasi_runtime
-        .fetch_module(wasi_module_path.as_path())
+    if modules.is_empty() && proxy.scripts.response.is_none() {
+        return Ok(resp);
+    }
+
+    let mut proxy_response = ProxyHttpResponse::new(resp, request_line).await?;
+    let mut stopped = false;
+    for module in modules.iter() {
+        let before = proxy
+            .diff_hooks
+            .then(|| Snapshot::response(&proxy_response.response));
+        let (next_response, module_stopped) = run_response_module(
+            wasi_runtime,
+            proxy_response,
+            module,
+            &proxy,
+            services.for_module(module),
+        )
         .await?;
+        proxy_response = next_response;
+        record_change(&services, &module.path, before, &proxy_response);
+        stopped = module_stopped;
+        if stopped {
+            break;
+        }
+    }
 
-    let mut linker: Linker<ResponseContext> = Linker::new(&wasi_runtime.engine);
-    let wasi = WasiCtxBuilder::new()
-        .inherit_stdio()
-        .inherit_args()?
-        .build();
+    if let Some(script) = proxy.scripts.response.as_ref().filter(|_script| !stopped) {
+        let before = proxy
+            .diff_hooks
+            .then(|| Snapshot::response(&proxy_response.response));
+        let original = proxy_response.clone();
+        proxy_response = match run_response_script(
+            wasi_runtime,
+            &services.scripts,
+            script,
+            &proxy.module_limits,
+            proxy_response,
+        )
+        .await
+        {
+            Ok(proxy_response) => proxy_response,
+            Err(err) => match proxy.module_limits.failure_policy {
+                FailurePolicy::Open => {
+                    tracing::warn!(
+                        ?err,
+                        script = ?script.path,
+                        "Response script failed, passing the response through."
+                    );
+                    original
+                }
+                FailurePolicy::Closed => return Err(err),
+            },
+        };
+        record_change(&services, &script.path, before, &proxy_response);
+    }
+
+    let new_response: Response<Body> = Response::try_from(proxy_response)?;
+    Ok(new_response)
+}
+
+/// Records what a stage changed about the response, when the proxy diffs its hooks.
+fn record_change(
+    services: &HostServices,
+    stage: &Path,
+    before: Option<Snapshot>,
+    proxy_response: &ProxyHttpResponse,
+) {
+    if let Some(before) = before {
+        let after = Snapshot::response(&proxy_response.response);
+        services
+            .log
+            .record_change(HookDiff::between("response", stage, &before, &after));
+    }
+}
+
+/// Runs a single stage of the pipeline, returning the response it leaves for the next stage
+/// and whether it stopped the pipeline.
+async fn run_response_module(
+    wasi_runtime: &mut WasiRuntime,
+    proxy_response: ProxyHttpResponse,
+    module: &WasiModule,
+    proxy: &Proxy,
+    services: HostServices,
+) -> Result<(ProxyHttpResponse, bool)> {
+    let original_response = proxy_response.response.clone();
+    let limits = proxy.module_limits.clone();
+    let wasi = build_wasi_ctx(&proxy.capabilities.response, &module.path)?;
     let ctx = ResponseContext {
         wasi,
         proxy_response,
-        config: ProxyConfig {
-            proxy,
-            error: "".into(),
-        },
+        config: ProxyConfig::for_module(proxy, module),
+        services,
+        limits: WasiRuntime::store_limits(&limits),
     };
 
     let mut store: Store<ResponseContext> = Store::new(&wasi_runtime.engine, ctx);
-    wasi_runtime::add_to_linker(&mut linker, |s| &mut s.wasi)?;
-
-    response::add_to_linker(&mut linker, |ctx| -> &mut ProxyHttpResponse {
-        &mut ctx.proxy_response
-    })?;
-
-    add_to_linker(&mut linker, |ctx| -> &mut ProxyConfig { &mut ctx.config })?;
+    store.limiter(|ctx| &mut ctx.limits);
+    wasi_runtime.apply_limits(&mut store, &limits)?;
+    let instance_pre = wasi_runtime
+        .instance_pre(&mut store, module.path.as_path(), |linker| {
+            wasi_runtime::add_to_linker(linker, |s: &mut ResponseContext| &mut s.wasi)?;
+            response::add_to_linker(linker, |ctx| -> &mut ProxyHttpResponse {
+                &mut ctx.proxy_response
+            })?;
+            add_to_linker(linker, |ctx| -> &mut ProxyConfig { &mut ctx.config })?;
+            HostServices::add_to_linker(linker, |ctx| -> &mut HostServices { &mut ctx.services })?;
+            Ok(())
+        })
+        .await?;
+    tracing::trace!("Linked module with WIT.");
 
-    linker.module(&mut store, "", &module)?;
-    linker
-        .get_default(&mut store, "")?
-        .typed::<(), (), _>(&store)?
-        .call(&mut store, ())?;
+    let (result, data) = wasi_runtime
+        .run_module(instance_pre, store, &limits)
+        .await?;
+    if let Err(err) = result {
+        return match limits.failure_policy {
+            FailurePolicy::Open => {
+                tracing::warn!(
+                    ?err,
+                    module = ?module.path,
+                    "Response module failed, passing the response through."
+                );
+                let original = ProxyHttpResponse {
+                    response: original_response,
+                };
+                Ok((original, false))
+            }
+            FailurePolicy::Closed => Err(err),
+        };
+    }
 
-    let data = store.into_data();
-    let new_response: Response<Body> = Response::try_from(data.proxy_response)?;
-    Ok(new_response)
+    Ok((data.proxy_response, data.services.pipeline.stopped()))
 }
@@ -0,0 +1,78 @@
+use anyhow::Result;
+use config::Proxy;
+use hyper::{Body, Request, Response};
+use wasi_runtime::WasiRuntime;
+
+use super::{
+    connection::Connection,
+    host::HostServices,
+    hostname::Hostname,
+    pre_request::{process_pre_request, PreRequestAction, ProxyHttpPreRequest},
+    request::{process_request, RequestOutcome},
+    response::{process_response, RequestLine},
+};
+
+/// Runs a proxy's modules one hook at a time, without listening for connections, for testing
+/// modules against fixtures. Modules can't make outbound requests, and get state of their own.
+pub struct ModuleRunner {
+    wasi_runtime: WasiRuntime,
+    proxy: Proxy,
+    services: HostServices,
+}
+
+impl ModuleRunner {
+    pub fn new(wasi_runtime: WasiRuntime, proxy: Proxy) -> Self {
+        Self {
+            wasi_runtime,
+            proxy,
+            services: HostServices::default(),
+        }
+    }
+
+    pub async fn pre_request(
+        &mut self,
+        req: &Request<Body>,
+        connection: &Connection,
+    ) -> Result<PreRequestAction> {
+        let hostname = Hostname::try_from(req)?;
+        let pre_request = ProxyHttpPreRequest::new(req, &hostname, connection);
+        process_pre_request(
+            &mut self.wasi_runtime,
+            pre_request,
+            self.proxy.pre_request_pipeline(),
+            self.proxy.clone(),
+            self.services.clone(),
+        )
+        .await
+    }
+
+    pub async fn request(&mut self, req: Request<Body>) -> Result<RequestOutcome> {
+        let hostname = Hostname::try_from(&req)?;
+        process_request(
+            &mut self.wasi_runtime,
+            req,
+            self.proxy.request_pipeline(),
+            &hostname.scheme,
+            &hostname.authority,
+            self.proxy.clone(),
+            self.services.clone(),
+        )
+        .await
+    }
+
+    pub async fn response(
+        &mut self,
+        resp: Response<Body>,
+        request_line: RequestLine,
+    ) -> Result<Response<Body>> {
+        process_response(
+            &mut self.wasi_runtime,
+            resp,
+            self.proxy.response_pipeline(),
+            self.proxy.clone(),
+            request_line,
+            self.services.clone(),
+        )
+        .await
+    }
+}
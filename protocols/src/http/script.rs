@@ -0,0 +1,811 @@
+//! Rhai scripts, run in a hook after its modules. Scripts see the same request or response as
+//! modules do through the WIT interfaces, and can make the same changes to it.
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use config::{ModuleLimits, Script};
+use proxysaur_wit_bindings::http::{
+    pre_request::{PreRequest, ProxyMode},
+    request::{self, Request},
+    response::Response,
+};
+use rhai::{Array, Blob, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use wasi_runtime::WasiRuntime;
+
+use super::{
+    pre_request::{PreRequestAction, ProxyHttpPreRequest},
+    request::ProxyHttpRequest,
+    response::ProxyHttpResponse,
+};
+
+type Headers = Vec<(String, String)>;
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Requests can't be cloned once they carry a response, so scripts share them with the engine
+/// instead.
+type Shared<T> = Arc<Mutex<T>>;
+
+/// How many operations a script may run when the proxy doesn't limit its modules' fuel.
+const DEFAULT_MAX_OPERATIONS: u64 = 1_000_000;
+
+/// How long a script may run when the proxy doesn't limit its modules' time.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Large enough for most bodies, which scripts see as strings.
+const MAX_STRING_SIZE: usize = 16 * 1024 * 1024;
+
+/// How many items arrays and maps built by scripts may hold.
+const MAX_COLLECTION_SIZE: usize = 100_000;
+
+thread_local! {
+    /// When the script running on this thread has to stop. Engines are shared between
+    /// requests, so the deadline of each run can't live in the engine.
+    static DEADLINE: Cell<Option<Instant>> = Cell::new(None);
+}
+
+fn lock<T>(shared: &Shared<T>) -> MutexGuard<'_, T> {
+    shared.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The first value of the header, or `()` when it's missing.
+fn header(headers: &Headers, name: &str) -> Dynamic {
+    headers
+        .iter()
+        .find(|(header, _value)| header.eq_ignore_ascii_case(name))
+        .map(|(_header, value)| Dynamic::from(value.clone()))
+        .unwrap_or(Dynamic::UNIT)
+}
+
+/// Every value of the header, in the order they were sent.
+fn header_values(headers: &Headers, name: &str) -> Array {
+    headers
+        .iter()
+        .filter(|(header, _value)| header.eq_ignore_ascii_case(name))
+        .map(|(_header, value)| Dynamic::from(value.clone()))
+        .collect()
+}
+
+/// The headers by lowercase name, keeping the first value of repeated headers. Scripts get the
+/// rest with `header_values`.
+fn headers_map(headers: &Headers) -> Map {
+    let mut map = Map::new();
+    for (name, value) in headers.iter() {
+        map.entry(name.to_lowercase().into())
+            .or_insert_with(|| Dynamic::from(value.clone()));
+    }
+    map
+}
+
+fn headers_from_map(map: Map) -> Headers {
+    map.into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Replaces every header called `name` with a single one.
+fn set_header(headers: &mut Headers, name: &str, value: &str) {
+    remove_header(headers, name);
+    headers.push((name.to_string(), value.to_string()));
+}
+
+fn remove_header(headers: &mut Headers, name: &str) {
+    headers.retain(|(header, _value)| !header.eq_ignore_ascii_case(name));
+}
+
+fn to_status(status: i64) -> ScriptResult<u16> {
+    u16::try_from(status).map_err(|_err| format!("Invalid status: {status}").into())
+}
+
+/// An engine held to the same limits as the proxy's modules, with `print` going to the log.
+/// Scripts are always limited, even when modules aren't.
+fn engine(script: &Script, limits: &ModuleLimits, register: fn(&mut Engine)) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(limits.fuel.unwrap_or(DEFAULT_MAX_OPERATIONS))
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        .on_progress(|_operations| {
+            DEADLINE
+                .with(Cell::get)
+                .filter(|deadline| Instant::now() > *deadline)
+                .map(|_deadline| Dynamic::from("Script timed out"))
+        });
+    let path = script.path.clone();
+    engine.on_print(move |text| tracing::info!(script = ?path, "{text}"));
+    register(&mut engine);
+    engine
+}
+
+/// A script compiled for one hook, along with the engine it runs on.
+struct Compiled {
+    source: String,
+    limits: ModuleLimits,
+    engine: Engine,
+    ast: AST,
+}
+
+/// Compiled scripts, by the hook they're compiled for and their path.
+type CompiledScripts = HashMap<(&'static str, PathBuf), Arc<Compiled>>;
+
+/// The compiled scripts of a proxy, so they're only compiled again once they change. Clones
+/// share the same scripts.
+#[derive(Clone, Default)]
+pub struct ScriptCache {
+    compiled: Arc<Mutex<CompiledScripts>>,
+}
+
+impl ScriptCache {
+    /// Compiles the script if it changed, then runs `work` with it on the blocking pool, in
+    /// one of the slots modules run in.
+    async fn run<R: Send + 'static>(
+        &self,
+        wasi_runtime: &WasiRuntime,
+        hook: Hook,
+        script: &Script,
+        limits: &ModuleLimits,
+        work: impl FnOnce(&Compiled, &Script) -> Result<R> + Send + 'static,
+    ) -> Result<R> {
+        let scripts = self.clone();
+        let script = script.clone();
+        let limits = limits.clone();
+        wasi_runtime
+            .run_blocking(move || {
+                let compiled = scripts.compiled(hook, &script, &limits)?;
+                work(&compiled, &script)
+            })
+            .await?
+    }
+
+    fn compiled(
+        &self,
+        hook: Hook,
+        script: &Script,
+        limits: &ModuleLimits,
+    ) -> Result<Arc<Compiled>> {
+        let source = script.source.as_deref().ok_or_else(|| {
+            anyhow::Error::msg(format!("Script {:?} hasn't been loaded", script.path))
+        })?;
+        let key = (hook.name, script.path.clone());
+        let cached = self
+            .lock()
+            .get(&key)
+            .filter(|compiled| compiled.source == source && compiled.limits == *limits)
+            .cloned();
+        if let Some(compiled) = cached {
+            return Ok(compiled);
+        }
+
+        let engine = engine(script, limits, hook.register);
+        let ast = engine.compile(source).map_err(|err| {
+            anyhow::Error::msg(format!("Script {:?} doesn't compile: {err}", script.path))
+        })?;
+        let compiled = Arc::new(Compiled {
+            source: source.to_string(),
+            limits: limits.clone(),
+            engine,
+            ast,
+        });
+        self.lock().insert(key, compiled.clone());
+        Ok(compiled)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CompiledScripts> {
+        self.compiled.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Compiled {
+    fn run(&self, script: &Script, scope: &mut Scope) -> Result<()> {
+        let timeout = self
+            .limits
+            .timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_TIMEOUT);
+        DEADLINE.with(|deadline| deadline.set(Some(Instant::now() + timeout)));
+        let result = self.engine.run_ast_with_scope(scope, &self.ast);
+        DEADLINE.with(|deadline| deadline.set(None));
+        result.map_err(|err| anyhow::Error::msg(format!("Script {:?} failed: {err}", script.path)))
+    }
+}
+
+/// A hook scripts run in, along with what its scripts can do.
+#[derive(Clone, Copy)]
+struct Hook {
+    name: &'static str,
+    register: fn(&mut Engine),
+}
+
+const PRE_REQUEST: Hook = Hook {
+    name: "pre-request",
+    register: register_pre_request,
+};
+
+fn register_pre_request(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<ProxyHttpPreRequest>("PreRequest")
+        .register_get("method", |req: &mut ProxyHttpPreRequest| {
+            req.request.method.clone()
+        })
+        .register_get("scheme", |req: &mut ProxyHttpPreRequest| {
+            req.request.scheme.clone()
+        })
+        .register_get("authority", |req: &mut ProxyHttpPreRequest| {
+            req.request.authority.clone()
+        })
+        .register_get("host", |req: &mut ProxyHttpPreRequest| {
+            req.request.host.clone()
+        })
+        .register_get("path", |req: &mut ProxyHttpPreRequest| {
+            req.request.path.clone()
+        })
+        .register_get("version", |req: &mut ProxyHttpPreRequest| {
+            req.request.version.clone()
+        })
+        .register_get("headers", |req: &mut ProxyHttpPreRequest| {
+            headers_map(&req.request.headers)
+        })
+        .register_get("client_address", |req: &mut ProxyHttpPreRequest| {
+            req.request.client_address.clone()
+        })
+        .register_get("listener_name", |req: &mut ProxyHttpPreRequest| {
+            req.request.listener_name.clone()
+        })
+        .register_get("listener_port", |req: &mut ProxyHttpPreRequest| {
+            i64::from(req.request.listener_port)
+        })
+        .register_fn("header", |req: &mut ProxyHttpPreRequest, name: &str| {
+            header(&req.request.headers, name)
+        })
+        .register_fn(
+            "header_values",
+            |req: &mut ProxyHttpPreRequest, name: &str| header_values(&req.request.headers, name),
+        )
+        .register_fn(
+            "set_proxy_mode",
+            |req: &mut ProxyHttpPreRequest, mode: &str| -> ScriptResult<()> {
+                let mode = match mode {
+                    "intercept" => ProxyMode::Intercept,
+                    "pass" => ProxyMode::Pass,
+                    _ => return Err(format!("Invalid proxy mode: {mode}").into()),
+                };
+                req.http_set_proxy_mode(mode);
+                Ok(())
+            },
+        )
+        .register_fn(
+            "block",
+            |req: &mut ProxyHttpPreRequest, status: i64| -> ScriptResult<()> {
+                Ok(req.http_block(to_status(status)?)?)
+            },
+        )
+        .register_fn(
+            "tunnel_to",
+            |req: &mut ProxyHttpPreRequest, address: &str| -> ScriptResult<()> {
+                Ok(req.http_tunnel_to(address)?)
+            },
+        );
+}
+
+/// Runs a pre-request script, returning what it decided to do with the request.
+pub(crate) async fn run_pre_request_script(
+    wasi_runtime: &WasiRuntime,
+    scripts: &ScriptCache,
+    script: &Script,
+    limits: &ModuleLimits,
+    proxy_request: ProxyHttpPreRequest,
+) -> Result<PreRequestAction> {
+    let work = move |compiled: &Compiled, script: &Script| {
+        let mut scope = Scope::new();
+        scope.push("request", proxy_request);
+        compiled.run(script, &mut scope)?;
+        let proxy_request = scope
+            .get_value::<ProxyHttpPreRequest>("request")
+            .ok_or_else(|| anyhow::Error::msg("Script replaced the request"))?;
+        Ok(proxy_request.action())
+    };
+    scripts
+        .run(wasi_runtime, PRE_REQUEST, script, limits, work)
+        .await
+}
+
+fn respond(
+    req: &mut Shared<ProxyHttpRequest>,
+    status: i64,
+    headers: Map,
+    body: &[u8],
+) -> ScriptResult<()> {
+    let headers = headers_from_map(headers);
+    let reply = request::HttpReplyParam {
+        status: to_status(status)?,
+        headers: headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect(),
+        body,
+    };
+    Ok(lock(req).http_request_respond(reply)?)
+}
+
+const REQUEST: Hook = Hook {
+    name: "request",
+    register: register_request,
+};
+
+fn register_request(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<Shared<ProxyHttpRequest>>("Request")
+        .register_get("method", |req: &mut Shared<ProxyHttpRequest>| {
+            lock(req).request.method.clone()
+        })
+        .register_get("scheme", |req: &mut Shared<ProxyHttpRequest>| {
+            lock(req).request.scheme.clone()
+        })
+        .register_get("authority", |req: &mut Shared<ProxyHttpRequest>| {
+            lock(req).request.authority.clone()
+        })
+        .register_get("host", |req: &mut Shared<ProxyHttpRequest>| {
+            lock(req).request.host.clone()
+        })
+        .register_get("path", |req: &mut Shared<ProxyHttpRequest>| {
+            lock(req).request.path.clone()
+        })
+        .register_get("version", |req: &mut Shared<ProxyHttpRequest>| {
+            lock(req).request.version.clone()
+        })
+        .register_get("headers", |req: &mut Shared<ProxyHttpRequest>| {
+            headers_map(&lock(req).request.headers)
+        })
+        .register_get("body", |req: &mut Shared<ProxyHttpRequest>| {
+            String::from_utf8_lossy(&lock(req).request.body).into_owned()
+        })
+        .register_fn(
+            "header",
+            |req: &mut Shared<ProxyHttpRequest>, name: &str| {
+                header(&lock(req).request.headers, name)
+            },
+        )
+        .register_fn(
+            "header_values",
+            |req: &mut Shared<ProxyHttpRequest>, name: &str| {
+                header_values(&lock(req).request.headers, name)
+            },
+        )
+        .register_fn(
+            "set_method",
+            |req: &mut Shared<ProxyHttpRequest>, method: &str| -> ScriptResult<()> {
+                Ok(lock(req).http_request_set_method(method)?)
+            },
+        )
+        .register_fn(
+            "set_uri",
+            |req: &mut Shared<ProxyHttpRequest>, uri: &str| -> ScriptResult<()> {
+                Ok(lock(req).http_request_set_uri(uri)?)
+            },
+        )
+        .register_fn(
+            "set_version",
+            |req: &mut Shared<ProxyHttpRequest>, version: &str| -> ScriptResult<()> {
+                Ok(lock(req).http_request_set_version(version)?)
+            },
+        )
+        .register_fn(
+            "set_header",
+            |req: &mut Shared<ProxyHttpRequest>, name: &str, value: &str| {
+                set_header(&mut lock(req).request.headers, name, value)
+            },
+        )
+        .register_fn(
+            "append_header",
+            |req: &mut Shared<ProxyHttpRequest>, name: &str, value: &str| {
+                lock(req)
+                    .request
+                    .headers
+                    .push((name.to_string(), value.to_string()))
+            },
+        )
+        .register_fn(
+            "remove_header",
+            |req: &mut Shared<ProxyHttpRequest>, name: &str| {
+                remove_header(&mut lock(req).request.headers, name)
+            },
+        )
+        .register_fn(
+            "set_body",
+            |req: &mut Shared<ProxyHttpRequest>, body: &str| {
+                lock(req).request.body = body.as_bytes().to_vec();
+            },
+        )
+        .register_fn(
+            "set_body",
+            |req: &mut Shared<ProxyHttpRequest>, body: Blob| {
+                lock(req).request.body = body;
+            },
+        )
+        .register_fn(
+            "respond",
+            |req: &mut Shared<ProxyHttpRequest>, status: i64, headers: Map, body: &str| {
+                respond(req, status, headers, body.as_bytes())
+            },
+        )
+        .register_fn(
+            "respond",
+            |req: &mut Shared<ProxyHttpRequest>, status: i64, headers: Map, body: Blob| {
+                respond(req, status, headers, &body)
+            },
+        )
+        .register_fn(
+            "respond",
+            |req: &mut Shared<ProxyHttpRequest>, status: i64, body: &str| {
+                respond(req, status, Map::new(), body.as_bytes())
+            },
+        );
+}
+
+/// Runs a request script, returning the request it leaves behind, along with the response when
+/// it answers the request.
+pub(crate) async fn run_request_script(
+    wasi_runtime: &WasiRuntime,
+    scripts: &ScriptCache,
+    script: &Script,
+    limits: &ModuleLimits,
+    proxy_request: ProxyHttpRequest,
+) -> Result<ProxyHttpRequest> {
+    let work = move |compiled: &Compiled, script: &Script| {
+        let proxy_request = Arc::new(Mutex::new(proxy_request));
+        let mut scope = Scope::new();
+        scope.push("request", proxy_request.clone());
+        compiled.run(script, &mut scope)?;
+        drop(scope);
+        let proxy_request = Arc::try_unwrap(proxy_request)
+            .map_err(|_request| anyhow::Error::msg("Script kept hold of the request"))?;
+        Ok(proxy_request
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner))
+    };
+    scripts
+        .run(wasi_runtime, REQUEST, script, limits, work)
+        .await
+}
+
+const RESPONSE: Hook = Hook {
+    name: "response",
+    register: register_response,
+};
+
+fn register_response(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<ProxyHttpResponse>("Response")
+        .register_get("status", |resp: &mut ProxyHttpResponse| {
+            i64::from(resp.response.status)
+        })
+        .register_get("headers", |resp: &mut ProxyHttpResponse| {
+            headers_map(&resp.response.headers)
+        })
+        .register_get("body", |resp: &mut ProxyHttpResponse| {
+            String::from_utf8_lossy(&resp.response.body).into_owned()
+        })
+        .register_get("request_method", |resp: &mut ProxyHttpResponse| {
+            resp.response.request_method.clone()
+        })
+        .register_get("request_scheme", |resp: &mut ProxyHttpResponse| {
+            resp.response.request_scheme.clone()
+        })
+        .register_get("request_authority", |resp: &mut ProxyHttpResponse| {
+            resp.response.request_authority.clone()
+        })
+        .register_get("request_host", |resp: &mut ProxyHttpResponse| {
+            resp.response.request_host.clone()
+        })
+        .register_get("request_path", |resp: &mut ProxyHttpResponse| {
+            resp.response.request_path.clone()
+        })
+        .register_fn("header", |resp: &mut ProxyHttpResponse, name: &str| {
+            header(&resp.response.headers, name)
+        })
+        .register_fn(
+            "header_values",
+            |resp: &mut ProxyHttpResponse, name: &str| header_values(&resp.response.headers, name),
+        )
+        .register_fn(
+            "set_status",
+            |resp: &mut ProxyHttpResponse, status: i64| -> ScriptResult<()> {
+                Ok(resp.http_response_set_status(to_status(status)?)?)
+            },
+        )
+        .register_fn(
+            "set_header",
+            |resp: &mut ProxyHttpResponse, name: &str, value: &str| {
+                set_header(&mut resp.response.headers, name, value)
+            },
+        )
+        .register_fn(
+            "append_header",
+            |resp: &mut ProxyHttpResponse, name: &str, value: &str| {
+                resp.response
+                    .headers
+                    .push((name.to_string(), value.to_string()))
+            },
+        )
+        .register_fn(
+            "remove_header",
+            |resp: &mut ProxyHttpResponse, name: &str| {
+                remove_header(&mut resp.response.headers, name)
+            },
+        )
+        .register_fn("set_body", |resp: &mut ProxyHttpResponse, body: &str| {
+            resp.response.body = body.as_bytes().to_vec();
+        })
+        .register_fn("set_body", |resp: &mut ProxyHttpResponse, body: Blob| {
+            resp.response.body = body;
+        });
+}
+
+/// Runs a response script, returning the response it leaves behind.
+pub(crate) async fn run_response_script(
+    wasi_runtime: &WasiRuntime,
+    scripts: &ScriptCache,
+    script: &Script,
+    limits: &ModuleLimits,
+    proxy_response: ProxyHttpResponse,
+) -> Result<ProxyHttpResponse> {
+    let work = move |compiled: &Compiled, script: &Script| {
+        let mut scope = Scope::new();
+        scope.push("response", proxy_response);
+        compiled.run(script, &mut scope)?;
+        scope
+            .get_value::<ProxyHttpResponse>("response")
+            .ok_or_else(|| anyhow::Error::msg("Script replaced the response"))
+    };
+    scripts
+        .run(wasi_runtime, RESPONSE, script, limits, work)
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use config::{ModuleLimits, Script};
+    use http::{Method, Request, Response, StatusCode, Uri, Version};
+    use hyper::Body;
+    use wasi_runtime::WasiRuntime;
+
+    use super::{run_pre_request_script, run_request_script, run_response_script, ScriptCache};
+    use crate::http::{
+        connection::Connection,
+        hostname::Hostname,
+        pre_request::{PreRequestAction, ProxyHttpPreRequest},
+        request::{ProxyHttpRequest, RequestOutcome},
+        response::{ProxyHttpResponse, RequestLine},
+    };
+
+    fn script(source: &str) -> Script {
+        Script {
+            path: PathBuf::from("/scripts/test.rhai"),
+            source: Some(source.into()),
+        }
+    }
+
+    fn runtime() -> WasiRuntime {
+        WasiRuntime::new(PathBuf::from("/")).expect("should build the runtime")
+    }
+
+    fn request() -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("http://localhost:8080/api?debug=1")
+            .header("Content-Type", "application/json")
+            .body(Body::from("{}"))
+            .expect("should build the request")
+    }
+
+    fn pre_request(req: &Request<Body>) -> ProxyHttpPreRequest {
+        let hostname = Hostname::try_from(req).expect("should parse the hostname");
+        let connection = Connection {
+            client_address: "127.0.0.1:5000".parse().expect("should parse the address"),
+            listener_name: "test".into(),
+            listener_port: 8080,
+        };
+        ProxyHttpPreRequest::new(req, &hostname, &connection)
+    }
+
+    #[tokio::test]
+    async fn blocks_requests() {
+        let script = script(
+            r#"
+            if request.path.starts_with("/api") && request.header("content-type") != () {
+                request.block(451);
+            } else {
+                request.set_proxy_mode("intercept");
+            }
+            "#,
+        );
+        let action = run_pre_request_script(
+            &runtime(),
+            &ScriptCache::default(),
+            &script,
+            &ModuleLimits::default(),
+            pre_request(&request()),
+        )
+        .await
+        .expect("should run the script");
+        assert!(matches!(
+            action,
+            PreRequestAction::Block(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS)
+        ));
+    }
+
+    #[tokio::test]
+    async fn modifies_and_answers_requests() {
+        let wasi_runtime = runtime();
+        let scripts = ScriptCache::default();
+        let proxy_request = ProxyHttpRequest::new(request(), "http", "localhost:8080")
+            .await
+            .expect("should build the request");
+        let script = script(
+            r#"
+            request.set_header("X-Script", request.method);
+            request.remove_header("content-type");
+            request.set_body("changed");
+            "#,
+        );
+        let proxy_request = run_request_script(
+            &wasi_runtime,
+            &scripts,
+            &script,
+            &ModuleLimits::default(),
+            proxy_request,
+        )
+        .await
+        .expect("should run the script");
+        let req = match proxy_request
+            .into_outcome()
+            .expect("should build the request")
+        {
+            RequestOutcome::Forward(req) => req,
+            RequestOutcome::Respond(..) => panic!("should forward the request"),
+        };
+        assert_eq!(req.headers()["x-script"], "POST");
+        assert!(req.headers().get("content-type").is_none());
+        let body = hyper::body::to_bytes(req.into_body())
+            .await
+            .expect("should read the body");
+        assert_eq!(body, "changed");
+
+        let proxy_request = ProxyHttpRequest::new(request(), "http", "localhost:8080")
+            .await
+            .expect("should build the request");
+        let script = script(r#"request.respond(418, #{"content-type": "text/plain"}, "mocked");"#);
+        let proxy_request = run_request_script(
+            &wasi_runtime,
+            &scripts,
+            &script,
+            &ModuleLimits::default(),
+            proxy_request,
+        )
+        .await
+        .expect("should run the script");
+        match proxy_request
+            .into_outcome()
+            .expect("should build the request")
+        {
+            RequestOutcome::Respond(_req, resp) => {
+                assert_eq!(resp.status(), StatusCode::IM_A_TEAPOT);
+                assert_eq!(resp.headers()["content-type"], "text/plain");
+            }
+            RequestOutcome::Forward(_req) => panic!("should answer the request"),
+        }
+    }
+
+    #[tokio::test]
+    async fn modifies_responses() {
+        let resp = Response::builder()
+            .status(500)
+            .body(Body::from("oops"))
+            .expect("should build the response");
+        let request_line = RequestLine {
+            method: Method::GET,
+            uri: Uri::from_static("http://localhost/api"),
+            version: Version::HTTP_11,
+        };
+        let proxy_response = ProxyHttpResponse::new(resp, request_line)
+            .await
+            .expect("should build the response");
+        let script = script(
+            r#"
+            if response.status >= 500 && response.request_path == "/api" {
+                response.set_status(200);
+                response.set_body(`{"recovered": true}`);
+            }
+            "#,
+        );
+        let proxy_response = run_response_script(
+            &runtime(),
+            &ScriptCache::default(),
+            &script,
+            &ModuleLimits::default(),
+            proxy_response,
+        )
+        .await
+        .expect("should run the script");
+        let resp = Response::try_from(proxy_response).expect("should build the response");
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn reads_every_header_value() {
+        let req = Request::builder()
+            .uri("http://localhost:8080/")
+            .header("Accept", "text/html")
+            .header("Accept", "application/json")
+            .body(Body::empty())
+            .expect("should build the request");
+        let proxy_request = ProxyHttpRequest::new(req, "http", "localhost:8080")
+            .await
+            .expect("should build the request");
+        let script = script(
+            r#"
+            let accepted = request.header_values("accept");
+            request.set_header("X-Accepted", accepted.len().to_string());
+            request.append_header("Accept", "text/plain");
+            "#,
+        );
+        let proxy_request = run_request_script(
+            &runtime(),
+            &ScriptCache::default(),
+            &script,
+            &ModuleLimits::default(),
+            proxy_request,
+        )
+        .await
+        .expect("should run the script");
+        let req = match proxy_request
+            .into_outcome()
+            .expect("should build the request")
+        {
+            RequestOutcome::Forward(req) => req,
+            RequestOutcome::Respond(..) => panic!("should forward the request"),
+        };
+        assert_eq!(req.headers()["x-accepted"], "2");
+        let accepted: Vec<_> = req.headers().get_all("accept").iter().collect();
+        assert_eq!(accepted, ["text/html", "application/json", "text/plain"]);
+    }
+
+    #[tokio::test]
+    async fn stops_runaway_scripts() {
+        let wasi_runtime = runtime();
+        let scripts = ScriptCache::default();
+        let limits = ModuleLimits {
+            fuel: Some(1000),
+            ..ModuleLimits::default()
+        };
+        let req = request();
+        let res = run_pre_request_script(
+            &wasi_runtime,
+            &scripts,
+            &script("loop {}"),
+            &limits,
+            pre_request(&req),
+        )
+        .await;
+        assert!(res.is_err());
+
+        // Scripts are limited even when modules aren't.
+        let res = run_pre_request_script(
+            &wasi_runtime,
+            &scripts,
+            &script("loop {}"),
+            &ModuleLimits::default(),
+            pre_request(&req),
+        )
+        .await;
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use proxysaur_wit_bindings::state::state;
+use serde::{Deserialize, Serialize};
+
+/// A value in the state store, along with when it expires.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StateEntry {
+    pub value: Vec<u8>,
+    /// Milliseconds since the Unix epoch, so expiry survives a restart when state is persisted
+    pub expires_at_ms: Option<u64>,
+}
+
+impl StateEntry {
+    fn is_expired(&self, now_ms: u64) -> bool {
+        self.expires_at_ms
+            .map(|expires_at_ms| expires_at_ms <= now_ms)
+            .unwrap_or(false)
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// How often changes are saved, so a burst of changes is written out once.
+const SAVE_INTERVAL: Duration = Duration::from_millis(500);
+
+fn expiry(ttl: Duration) -> u64 {
+    now_ms().saturating_add(ttl.as_millis() as u64)
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<String, StateEntry>,
+    /// Whether there are changes that haven't been saved yet
+    dirty: bool,
+}
+
+impl Entries {
+    fn live(&mut self, key: &str) -> Option<&mut StateEntry> {
+        let now_ms = now_ms();
+        if self
+            .entries
+            .get(key)
+            .map(|entry| entry.is_expired(now_ms))
+            .unwrap_or(false)
+        {
+            self.entries.remove(key);
+        }
+        self.entries.get_mut(key)
+    }
+
+    /// Serializes the unsaved changes, dropping expired entries along the way.
+    fn take_changes(&mut self) -> Option<Result<Vec<u8>>> {
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        let now_ms = now_ms();
+        self.entries.retain(|_key, entry| !entry.is_expired(now_ms));
+        Some(serde_json::to_vec_pretty(&self.entries).map_err(anyhow::Error::from))
+    }
+}
+
+/// Written to the side and renamed, so a crash never leaves a truncated file.
+async fn write_atomically(path: &Path, contents: Vec<u8>) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, contents).await?;
+    tokio::fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+/// Key-value state shared by all of a proxy's modules, so they can remember things across
+/// requests. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct StateStore {
+    inner: Arc<Mutex<Entries>>,
+    persist_path: Option<Arc<PathBuf>>,
+}
+
+impl StateStore {
+    /// Loads the state saved at `persist_path`, which changes are then saved to in the
+    /// background. Without a path, state only lives in memory.
+    pub async fn open(persist_path: Option<PathBuf>) -> Result<Self> {
+        let mut entries = HashMap::new();
+        if let Some(path) = persist_path.as_ref() {
+            match tokio::fs::read(path).await {
+                Ok(contents) => entries = serde_json::from_slice(&contents)?,
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let store = Self {
+            inner: Arc::new(Mutex::new(Entries {
+                entries,
+                dirty: false,
+            })),
+            persist_path: persist_path.map(Arc::new),
+        };
+        if store.persist_path.is_some() {
+            store.spawn_saver();
+        }
+        Ok(store)
+    }
+
+    /// Saves changes until every clone of the store is dropped. Modules change state from
+    /// blocking threads, so the file is never written while they wait.
+    fn spawn_saver(&self) {
+        let inner = Arc::downgrade(&self.inner);
+        let persist_path = self.persist_path.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SAVE_INTERVAL);
+            loop {
+                interval.tick().await;
+                let store = match inner.upgrade() {
+                    Some(inner) => StateStore {
+                        inner,
+                        persist_path: persist_path.clone(),
+                    },
+                    None => return,
+                };
+                store.save().await;
+            }
+        });
+    }
+
+    /// Writes out any unsaved changes. Failing to save is logged rather than surfaced to
+    /// modules, since the in-memory state is still correct, and retried on the next save.
+    pub async fn save(&self) {
+        let path = match self.persist_path.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+        let changes = self.lock().take_changes();
+        let result = match changes {
+            Some(Ok(contents)) => write_atomically(path, contents).await,
+            Some(Err(err)) => Err(err),
+            None => return,
+        };
+        if let Err(err) = result {
+            tracing::error!(?path, ?err, "Error saving module state.");
+            self.lock().dirty = true;
+        }
+    }
+
+    // Host functions run on blocking threads, so a std mutex is fine here
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.lock().live(key).map(|entry| entry.value.clone())
+    }
+
+    /// Stores `value` under `key`, expiring it after `ttl` if given.
+    pub fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) {
+        tracing::debug!(key, ?ttl, "Set state.");
+        let mut entries = self.lock();
+        let entry = StateEntry {
+            value,
+            expires_at_ms: ttl.map(expiry),
+        };
+        entries.entries.insert(key.to_string(), entry);
+        entries.dirty = true;
+    }
+
+    pub fn delete(&self, key: &str) {
+        tracing::debug!(key, "Deleted state.");
+        let mut entries = self.lock();
+        if entries.entries.remove(key).is_some() {
+            entries.dirty = true;
+        }
+    }
+
+    /// Adds `amount` to the integer stored under `key`, starting from zero when there's
+    /// nothing stored. A `ttl` replaces the entry's expiry, otherwise it's kept.
+    pub fn increment(&self, key: &str, amount: i64, ttl: Option<Duration>) -> Result<i64, String> {
+        let mut entries = self.lock();
+        let (current, expires_at_ms) = match entries.live(key) {
+            Some(entry) => {
+                let current = std::str::from_utf8(&entry.value)
+                    .ok()
+                    .and_then(|value| value.parse::<i64>().ok())
+                    .ok_or_else(|| format!("The value of {key} is not an integer"))?;
+                (current, entry.expires_at_ms)
+            }
+            None => (0, None),
+        };
+        let value = current
+            .checked_add(amount)
+            .ok_or_else(|| format!("Incrementing {key} overflows"))?;
+        tracing::debug!(key, value, ?ttl, "Incremented state.");
+        let entry = StateEntry {
+            value: value.to_string().into_bytes(),
+            expires_at_ms: ttl.map(expiry).or(expires_at_ms),
+        };
+        entries.entries.insert(key.to_string(), entry);
+        entries.dirty = true;
+        Ok(value)
+    }
+
+    /// Everything currently stored.
+    pub fn snapshot(&self) -> HashMap<String, StateEntry> {
+        let now_ms = now_ms();
+        self.lock()
+            .entries
+            .iter()
+            .filter(|(_key, entry)| !entry.is_expired(now_ms))
+            .map(|(key, entry)| (key.clone(), entry.clone()))
+            .collect()
+    }
+}
+
+impl std::fmt::Debug for StateStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let snapshot = self.snapshot();
+        let mut map = f.debug_map();
+        for (key, entry) in snapshot.iter() {
+            map.entry(key, &String::from_utf8_lossy(&entry.value));
+        }
+        map.finish()
+    }
+}
+
+impl state::State for StateStore {
+    fn state_get(&mut self, key: &str) -> Option<state::StateValueResult> {
+        self.get(key)
+    }
+
+    fn state_set(&mut self, key: &str, value: state::StateValueParam<'_>, ttl_ms: Option<u64>) {
+        self.set(key, value.to_vec(), ttl_ms.map(Duration::from_millis))
+    }
+
+    fn state_delete(&mut self, key: &str) {
+        self.delete(key)
+    }
+
+    fn state_increment(
+        &mut self,
+        key: &str,
+        amount: i64,
+        ttl_ms: Option<u64>,
+    ) -> Result<i64, state::Error> {
+        self.increment(key, amount, ttl_ms.map(Duration::from_millis))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::StateStore;
+
+    #[test]
+    fn expires_entries() {
+        let store = StateStore::default();
+        store.set(
+            "session",
+            b"token".to_vec(),
+            Some(Duration::from_millis(20)),
+        );
+        store.set("forever", b"value".to_vec(), None);
+        assert_eq!(store.get("session"), Some(b"token".to_vec()));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(store.get("session"), None);
+        assert_eq!(store.get("forever"), Some(b"value".to_vec()));
+    }
+
+    #[test]
+    fn increments_integers() {
+        let store = StateStore::default();
+        assert_eq!(store.increment("hits", 1, None), Ok(1));
+        assert_eq!(store.increment("hits", 5, None), Ok(6));
+        assert_eq!(store.increment("hits", -10, None), Ok(-4));
+        assert_eq!(store.get("hits"), Some(b"-4".to_vec()));
+
+        store.set("name", b"proxysaur".to_vec(), None);
+        assert!(store.increment("name", 1, None).is_err());
+        store.delete("hits");
+        assert_eq!(store.get("hits"), None);
+    }
+
+    #[tokio::test]
+    async fn persists_across_restarts() {
+        let dir = tempfile::Builder::new()
+            .prefix("proxysaur-state")
+            .tempdir()
+            .expect("should create a temp dir");
+        let path = dir.path().join("state.json");
+
+        let store = StateStore::open(Some(path.clone()))
+            .await
+            .expect("should open the store");
+        store.set("token", b"abc".to_vec(), None);
+        store.increment("count", 2, None).expect("should increment");
+        assert!(!path.exists(), "should save in the background");
+        store.save().await;
+
+        let store = StateStore::open(Some(path))
+            .await
+            .expect("should reopen the store");
+        assert_eq!(store.get("token"), Some(b"abc".to_vec()));
+        assert_eq!(store.get("count"), Some(b"2".to_vec()));
+    }
+}
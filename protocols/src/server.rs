@@ -0,0 +1,311 @@
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use config::{Protocol, Proxy};
+use tempfile::TempDir;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::watch,
+    task::JoinHandle,
+};
+
+use crate::{
+    http::{
+        interceptor::{HttpInterceptor, PassThrough},
+        proxy::{http_forward, http_proxy, HttpContext},
+        state::StateStore,
+    },
+    tcp::tunnel,
+};
+
+/// How long a listener waits after failing to accept a connection. Errors like running out of
+/// file descriptors persist until connections close, so retrying right away would spin.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Serves a connection accepted on `proxy`'s listener, according to its protocol.
+pub async fn serve_connection(
+    mut socket: TcpStream,
+    proxy: Proxy,
+    interceptor: Arc<dyn HttpInterceptor>,
+    context: HttpContext,
+) -> Result<()> {
+    match proxy.protocol {
+        Protocol::Tcp => tunnel(&mut socket, &proxy.upstream_address()).await,
+        Protocol::HttpForward => http_forward(socket, proxy, interceptor, context).await,
+        Protocol::Http => http_proxy(socket, proxy, interceptor, context).await,
+    }
+}
+
+/// Stops a [`ProxyServer`] from accepting connections. Connections already accepted are left to
+/// finish.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        let _res = self.sender.send(true);
+    }
+}
+
+/// Proxies started from code rather than the command line, for embedding proxysaur in other
+/// programs and tests.
+///
+/// Listeners stop once the server is shut down, or once the server and all of its shutdown
+/// handles are dropped.
+pub struct ProxyServer {
+    addresses: Vec<SocketAddr>,
+    ca_certificate: Vec<u8>,
+    shutdown: ShutdownHandle,
+    listeners: Vec<JoinHandle<()>>,
+    // Removed once the server is dropped
+    _ca_dir: Option<TempDir>,
+}
+
+impl ProxyServer {
+    pub fn builder() -> ProxyServerBuilder {
+        ProxyServerBuilder::default()
+    }
+
+    /// The address the first listener is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addresses[0]
+    }
+
+    /// The addresses of the listeners, in the order they were added.
+    pub fn addresses(&self) -> &[SocketAddr] {
+        &self.addresses
+    }
+
+    /// The PEM encoded root certificate clients need to trust to have HTTPS intercepted.
+    pub fn ca_certificate(&self) -> &[u8] {
+        &self.ca_certificate
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Stops accepting connections, waiting for the listeners to close.
+    pub async fn shutdown(self) {
+        self.shutdown.shutdown();
+        for listener in self.listeners {
+            if let Err(err) = listener.await {
+                tracing::error!(?err, "Error stopping listener.");
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ProxyServerBuilder {
+    listeners: Vec<Proxy>,
+    ca_path: Option<PathBuf>,
+    interceptor: Option<Arc<dyn HttpInterceptor>>,
+}
+
+impl ProxyServerBuilder {
+    /// Adds a listener for `proxy`. Its port can be 0 to bind any free port, which
+    /// [`ProxyServer::addresses`] then reports.
+    pub fn listener(mut self, proxy: Proxy) -> Self {
+        self.listeners.push(proxy);
+        self
+    }
+
+    /// Signs intercepted HTTPS connections with the CA in `ca_path`, which is generated if it
+    /// isn't there. Without it, a new CA is generated in a temporary directory.
+    pub fn ca(mut self, ca_path: impl Into<PathBuf>) -> Self {
+        self.ca_path = Some(ca_path.into());
+        self
+    }
+
+    /// Intercepts requests with `interceptor`. Without it, requests are passed through as is.
+    pub fn interceptor(mut self, interceptor: impl HttpInterceptor) -> Self {
+        self.interceptor = Some(Arc::new(interceptor));
+        self
+    }
+
+    /// Binds every listener and starts accepting connections in the background.
+    pub async fn start(self) -> Result<ProxyServer> {
+        if self.listeners.is_empty() {
+            return Err(anyhow::Error::msg("No listeners to start"));
+        }
+
+        let (ca_path, ca_dir) = match self.ca_path {
+            Some(ca_path) => (ca_path, None),
+            None => {
+                let ca_dir = tempfile::Builder::new().prefix("proxysaur-ca").tempdir()?;
+                (ca_dir.path().to_path_buf(), Some(ca_dir))
+            }
+        };
+        let ca_path = ca::generate_ca(Some(ca_path), false).await?;
+        let context = HttpContext::new(ca_path.as_path()).await?;
+        let ca_certificate = context.ca_certificate().await?;
+        let interceptor = self.interceptor.unwrap_or_else(|| Arc::new(PassThrough));
+
+        let (sender, receiver) = watch::channel(false);
+        let mut addresses = Vec::with_capacity(self.listeners.len());
+        let mut listeners = Vec::with_capacity(self.listeners.len());
+        for proxy in self.listeners {
+            let listener = TcpListener::bind(&proxy.address()).await?;
+            addresses.push(listener.local_addr()?);
+            // Each proxy's modules share state with each other, but not with other proxies
+            let state = StateStore::open(proxy.state.persist_path.clone()).await?;
+            let context = context.clone().with_state(state);
+            listeners.push(tokio::spawn(accept(
+                listener,
+                proxy,
+                interceptor.clone(),
+                context,
+                receiver.clone(),
+            )));
+        }
+
+        Ok(ProxyServer {
+            addresses,
+            ca_certificate,
+            shutdown: ShutdownHandle {
+                sender: Arc::new(sender),
+            },
+            listeners,
+            _ca_dir: ca_dir,
+        })
+    }
+}
+
+async fn accept(
+    listener: TcpListener,
+    proxy: Proxy,
+    interceptor: Arc<dyn HttpInterceptor>,
+    context: HttpContext,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            // Also stops once every handle is dropped
+            _ = shutdown.changed() => break,
+        };
+        let socket = match accepted {
+            Ok((socket, _)) => socket,
+            Err(err) => {
+                tracing::error!(?err, "Error accepting connection.");
+                tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+        let proxy = proxy.clone();
+        let interceptor = interceptor.clone();
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(err) = serve_connection(socket, proxy, interceptor, context).await {
+                tracing::error!(?err, "Error proxying the connection");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use config::Proxy;
+    use hyper::{Body, Request, Response};
+    use tokio::net::TcpStream;
+
+    use super::ProxyServer;
+    use crate::http::{
+        interceptor::{FnInterceptor, HttpInterceptor, RequestOutcome},
+        proxy::HttpContext,
+    };
+
+    struct Mock;
+
+    #[async_trait]
+    impl HttpInterceptor for Mock {
+        async fn on_request(
+            &self,
+            req: Request<Body>,
+            _proxy: &Proxy,
+            _context: &HttpContext,
+        ) -> Result<RequestOutcome> {
+            let body = format!("mocked {}", req.uri().path());
+            Ok(RequestOutcome::Respond(
+                req,
+                Response::new(Body::from(body)),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn starts_and_shuts_down() {
+        let mut proxy = Proxy::new();
+        proxy.address = "127.0.0.1".into();
+        proxy.port = Some(0);
+        let server = ProxyServer::builder()
+            .listener(proxy)
+            .interceptor(Mock)
+            .start()
+            .await
+            .expect("should start the server");
+        let address = server.local_addr();
+        assert_ne!(address.port(), 0);
+        assert!(server
+            .ca_certificate()
+            .starts_with(b"-----BEGIN CERTIFICATE-----"));
+
+        let uri = format!("http://{address}/hello");
+        let response = hyper::Client::new()
+            .get(uri.parse().expect("should parse the URI"))
+            .await
+            .expect("should make the request");
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .expect("should read the body");
+        assert_eq!(&body[..], b"mocked /hello");
+
+        server.shutdown().await;
+        assert!(TcpStream::connect(address).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn intercepts_with_closures() {
+        let mut proxy = Proxy::new();
+        proxy.address = "127.0.0.1".into();
+        proxy.port = Some(0);
+        let interceptor = FnInterceptor::new()
+            .with_on_request(|req| {
+                let body = format!("closure {}", req.uri().path());
+                RequestOutcome::Respond(req, Response::new(Body::from(body)))
+            })
+            .with_on_response(|mut resp, request_line| {
+                let path = request_line
+                    .uri
+                    .path()
+                    .parse()
+                    .expect("should be a header value");
+                resp.headers_mut().insert("x-path", path);
+                resp
+            });
+        let server = ProxyServer::builder()
+            .listener(proxy)
+            .interceptor(interceptor)
+            .start()
+            .await
+            .expect("should start the server");
+
+        let uri = format!("http://{}/hello", server.local_addr());
+        let response = hyper::Client::new()
+            .get(uri.parse().expect("should parse the URI"))
+            .await
+            .expect("should make the request");
+        assert_eq!(response.headers()["x-path"], "/hello");
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .expect("should read the body");
+        assert_eq!(&body[..], b"closure /hello");
+
+        server.shutdown().await;
+    }
+}
@@ -0,0 +1,44 @@
+//! Attribute macros that turn a handler function into a proxysaur module's entry point.
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn};
+
+/// Generates a `main` that passes the handler what it handles, then hands back what it returns.
+fn entry_point(attr: TokenStream, item: TokenStream, runner: &str) -> TokenStream {
+    if !attr.is_empty() {
+        let attr = proc_macro2::TokenStream::from(attr);
+        return syn::Error::new_spanned(attr, "this attribute takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+    let handler = parse_macro_input!(item as ItemFn);
+    let name = &handler.sig.ident;
+    let runner = syn::Ident::new(runner, proc_macro2::Span::call_site());
+    let expanded = quote! {
+        #handler
+
+        fn main() {
+            ::proxysaur::rt::#runner(#name)
+        }
+    };
+    expanded.into()
+}
+
+/// Marks `fn(Request) -> Result<impl Into<RequestOutcome>, impl Display>` as the module's
+/// request handler.
+#[proc_macro_attribute]
+pub fn on_request(attr: TokenStream, item: TokenStream) -> TokenStream {
+    entry_point(attr, item, "run_request")
+}
+
+/// Marks `fn(Response) -> Result<Response, impl Display>` as the module's response handler.
+#[proc_macro_attribute]
+pub fn on_response(attr: TokenStream, item: TokenStream) -> TokenStream {
+    entry_point(attr, item, "run_response")
+}
+
+/// Marks `fn(PreRequest) -> Result<Action, impl Display>` as the module's pre-request handler.
+#[proc_macro_attribute]
+pub fn pre_request(attr: TokenStream, item: TokenStream) -> TokenStream {
+    entry_point(attr, item, "run_pre_request")
+}
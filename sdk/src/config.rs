@@ -0,0 +1,38 @@
+//! The module's configuration, as set by the proxy it runs in.
+//!
+//! Configuration that can't be read is reported to the proxy, besides being returned.
+use proxysaur_bindings::config;
+use serde::de::DeserializeOwned;
+
+use crate::{Error, Result};
+
+/// The configuration as is.
+pub fn raw() -> Vec<u8> {
+    config::get_config_data()
+}
+
+/// Tells the proxy the configuration is invalid.
+pub fn invalid(message: &str) {
+    config::set_invalid_data(message);
+}
+
+fn parse<T, E>(parse: impl FnOnce(&[u8]) -> Result<T, E>) -> Result<T>
+where
+    Error: From<E>,
+{
+    parse(&raw()).map_err(|err| {
+        let err = Error::from(err);
+        invalid(&err.to_string());
+        err
+    })
+}
+
+/// Deserializes the configuration from JSON.
+pub fn json<T: DeserializeOwned>() -> Result<T> {
+    parse(serde_json::from_slice)
+}
+
+/// Deserializes the configuration from YAML. JSON is valid YAML, so this reads either.
+pub fn yaml<T: DeserializeOwned>() -> Result<T> {
+    parse(serde_yaml::from_slice)
+}
@@ -0,0 +1,140 @@
+/// HTTP headers, in the order they were sent. Names are matched without regard to case, and a
+/// name can appear more than once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    headers: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The first value of `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_all(name).next()
+    }
+
+    /// Every value of `name`, in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(header, _value)| header.eq_ignore_ascii_case(name))
+            .map(|(_header, value)| value.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Sets `name` to `value`, replacing any values it had.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        let value = value.into();
+        match self
+            .headers
+            .iter()
+            .position(|(header, _value)| header.eq_ignore_ascii_case(&name))
+        {
+            // Keeps the header where it was, dropping its other values
+            Some(idx) => {
+                self.headers[idx].1 = value;
+                let rest = self.headers.split_off(idx + 1);
+                self.headers.extend(
+                    rest.into_iter()
+                        .filter(|(header, _value)| !header.eq_ignore_ascii_case(&name)),
+                );
+            }
+            None => self.headers.push((name, value)),
+        }
+    }
+
+    /// Adds a value for `name`, keeping any it had.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.headers.push((name.into(), value.into()));
+    }
+
+    /// Removes every value of `name`, returning whether there were any.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.headers.len();
+        self.headers
+            .retain(|(header, _value)| !header.eq_ignore_ascii_case(name));
+        self.headers.len() != len
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// The headers as the bindings take them.
+    pub(crate) fn as_params(&self) -> Vec<(&str, &str)> {
+        self.iter().collect()
+    }
+}
+
+impl From<Vec<(String, String)>> for Headers {
+    fn from(headers: Vec<(String, String)>) -> Self {
+        Self { headers }
+    }
+}
+
+impl From<Headers> for Vec<(String, String)> {
+    fn from(headers: Headers) -> Self {
+        headers.headers
+    }
+}
+
+impl<N: Into<String>, V: Into<String>> FromIterator<(N, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+        Self {
+            headers: iter
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Headers;
+
+    #[test]
+    fn matches_names_without_case() {
+        let mut headers: Headers = [
+            ("Content-Type", "text/html"),
+            ("Set-Cookie", "a=1"),
+            ("set-cookie", "b=2"),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(headers.get("content-type"), Some("text/html"));
+        assert_eq!(
+            headers.get_all("SET-COOKIE").collect::<Vec<&str>>(),
+            vec!["a=1", "b=2"]
+        );
+
+        headers.insert("set-cookie", "c=3");
+        assert_eq!(
+            headers.get_all("set-cookie").collect::<Vec<&str>>(),
+            vec!["c=3"]
+        );
+        assert_eq!(headers.len(), 2);
+
+        headers.append("Accept", "text/html");
+        headers.append("accept", "application/json");
+        assert!(headers.remove("ACCEPT"));
+        assert!(!headers.contains("accept"));
+        assert!(!headers.remove("accept"));
+    }
+}
@@ -0,0 +1,24 @@
+//! Write proxysaur modules against typed requests and responses.
+//!
+//! A module is a binary with one handler, marked with [`on_request`], [`on_response`] or
+//! [`pre_request`] depending on the hook it runs in.
+mod error;
+mod headers;
+mod pre_request;
+mod request;
+mod response;
+mod route;
+
+pub mod config;
+#[doc(hidden)]
+pub mod rt;
+
+pub use error::{Error, Result};
+pub use headers::Headers;
+pub use pre_request::{Action, PreRequest};
+pub use request::{Request, RequestOutcome};
+pub use response::{RequestInfo, Response};
+pub use route::Route;
+
+pub use proxysaur_bindings::{log, pipeline, state};
+pub use proxysaur_macros::{on_request, on_response, pre_request};
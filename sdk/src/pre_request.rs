@@ -0,0 +1,72 @@
+use proxysaur_bindings::http::pre_request::{self, ProxyMode};
+
+use crate::{Headers, Result, Route};
+
+/// A request a forward proxy hasn't decided what to do with yet. Bodies aren't read until
+/// then, so there isn't one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreRequest {
+    pub method: String,
+    pub scheme: String,
+    pub authority: String,
+    pub host: String,
+    /// The path, along with the query string
+    pub path: String,
+    pub version: String,
+    pub headers: Headers,
+    /// The address of the client that sent the request
+    pub client_address: String,
+    pub listener_name: String,
+    pub listener_port: u16,
+}
+
+/// What the proxy does with a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Runs the request and response modules on the request.
+    Intercept,
+    /// Sends the request on untouched.
+    Pass,
+    /// Answers the request with a status, without sending it on.
+    Block(u16),
+    /// Sends the request on untouched to another `host:port`.
+    TunnelTo(String),
+}
+
+impl PreRequest {
+    /// The request being handled.
+    pub fn get() -> Self {
+        let request = pre_request::http_request_get();
+        Self {
+            method: request.method,
+            scheme: request.scheme,
+            authority: request.authority,
+            host: request.host,
+            path: request.path,
+            version: request.version,
+            headers: Headers::from(request.headers),
+            client_address: request.client_address,
+            listener_name: request.listener_name,
+            listener_port: request.listener_port,
+        }
+    }
+}
+
+impl Route for PreRequest {
+    fn path_and_query(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Action {
+    /// Tells the proxy what to do with the request being handled.
+    pub fn set(&self) -> Result<()> {
+        match self {
+            Action::Intercept => pre_request::http_set_proxy_mode(ProxyMode::Intercept),
+            Action::Pass => pre_request::http_set_proxy_mode(ProxyMode::Pass),
+            Action::Block(status) => pre_request::http_block(*status)?,
+            Action::TunnelTo(address) => pre_request::http_tunnel_to(address)?,
+        }
+        Ok(())
+    }
+}
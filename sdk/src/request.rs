@@ -0,0 +1,98 @@
+use proxysaur_bindings::http::request;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Headers, Response, Result, Route};
+
+/// The request an intercepted client sent, which is sent upstream once the module is done.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub scheme: String,
+    /// `host:port`
+    pub authority: String,
+    pub host: String,
+    /// The path, along with the query string
+    pub path: String,
+    pub version: String,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+/// What a request handler does with the request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequestOutcome {
+    /// Sends the request upstream.
+    Forward(Request),
+    /// Answers the request without contacting the upstream.
+    Respond(Response),
+}
+
+impl From<Request> for RequestOutcome {
+    fn from(request: Request) -> Self {
+        RequestOutcome::Forward(request)
+    }
+}
+
+impl From<Response> for RequestOutcome {
+    fn from(response: Response) -> Self {
+        RequestOutcome::Respond(response)
+    }
+}
+
+impl Request {
+    /// The request being handled.
+    pub fn get() -> Result<Self> {
+        let request = request::http_request_get()?;
+        Ok(Self {
+            method: request.method,
+            scheme: request.scheme,
+            authority: request.authority,
+            host: request.host,
+            path: request.path,
+            version: request.version,
+            headers: Headers::from(request.headers),
+            body: request.body,
+        })
+    }
+
+    /// Sends this request upstream in place of the one being handled.
+    pub fn set(&self) {
+        let headers = self.headers.as_params();
+        request::http_request_set(request::HttpRequestParam {
+            method: &self.method,
+            scheme: &self.scheme,
+            authority: &self.authority,
+            host: &self.host,
+            path: &self.path,
+            version: &self.version,
+            headers: &headers,
+            body: &self.body,
+        });
+    }
+
+    pub fn text(&self) -> Result<&str> {
+        Ok(std::str::from_utf8(&self.body)?)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    /// Replaces the body, keeping the headers as they are.
+    pub fn set_body(&mut self, body: impl Into<Vec<u8>>) {
+        self.body = body.into();
+    }
+
+    /// Replaces the body with `value` as JSON, and sets the content type to match.
+    pub fn set_json<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        self.body = serde_json::to_vec(value)?;
+        self.headers.insert("content-type", "application/json");
+        Ok(())
+    }
+}
+
+impl Route for Request {
+    fn path_and_query(&self) -> &str {
+        &self.path
+    }
+}
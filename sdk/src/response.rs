@@ -0,0 +1,123 @@
+use proxysaur_bindings::http::{request, response};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Headers, Result, Route};
+
+/// The request a response answers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestInfo {
+    pub method: String,
+    pub scheme: String,
+    pub authority: String,
+    pub host: String,
+    /// The path, along with the query string
+    pub path: String,
+    pub version: String,
+    pub headers: Headers,
+}
+
+impl Route for RequestInfo {
+    fn path_and_query(&self) -> &str {
+        &self.path
+    }
+}
+
+/// A response from the upstream, or one a module answers a request with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Headers,
+    pub body: Vec<u8>,
+    request: RequestInfo,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: Headers::new(),
+            body: vec![],
+            request: RequestInfo::default(),
+        }
+    }
+
+    /// A response with `value` as its JSON body.
+    pub fn from_json<T: Serialize>(status: u16, value: &T) -> Result<Self> {
+        let mut response = Self::new(status);
+        response.set_json(value)?;
+        Ok(response)
+    }
+
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// The response being handled, along with the request it answers.
+    pub fn get() -> Result<Self> {
+        let response = response::http_response_get()?;
+        Ok(Self {
+            status: response.status,
+            headers: Headers::from(response.headers),
+            body: response.body,
+            request: RequestInfo {
+                method: response.request_method,
+                scheme: response.request_scheme,
+                authority: response.request_authority,
+                host: response.request_host,
+                path: response.request_path,
+                version: response.request_version,
+                headers: Headers::from(response.request_headers),
+            },
+        })
+    }
+
+    /// Returns this response to the client in place of the one being handled.
+    pub fn set(&self) -> Result<()> {
+        response::http_response_set_status(self.status)?;
+        response::http_response_set_headers(&self.headers.as_params())?;
+        response::http_response_set_body(&self.body)?;
+        Ok(())
+    }
+
+    /// Answers the request being handled with this response.
+    pub fn respond(&self) -> Result<()> {
+        let headers = self.headers.as_params();
+        request::http_request_respond(request::HttpReply {
+            status: self.status,
+            headers: &headers,
+            body: &self.body,
+        })?;
+        Ok(())
+    }
+
+    /// The request this response answers. Empty for responses made by a module.
+    pub fn request(&self) -> &RequestInfo {
+        &self.request
+    }
+
+    pub fn text(&self) -> Result<&str> {
+        Ok(std::str::from_utf8(&self.body)?)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    /// Replaces the body, keeping the headers as they are.
+    pub fn set_body(&mut self, body: impl Into<Vec<u8>>) {
+        self.body = body.into();
+    }
+
+    /// Replaces the body with `value` as JSON, and sets the content type to match.
+    pub fn set_json<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        self.body = serde_json::to_vec(value)?;
+        self.headers.insert("content-type", "application/json");
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+/// Reads the route and query string of anything with a path, like a [`Request`](crate::Request),
+/// a [`PreRequest`](crate::PreRequest) or the [`RequestInfo`](crate::RequestInfo) of a response.
+pub trait Route {
+    /// The path, along with the query string.
+    fn path_and_query(&self) -> &str;
+
+    /// The path without the query string.
+    fn route(&self) -> &str {
+        split_query(self.path_and_query()).0
+    }
+
+    /// The query string, without the `?`.
+    fn query(&self) -> Option<&str> {
+        split_query(self.path_and_query()).1
+    }
+
+    /// The decoded parameters of the query string, in order.
+    fn query_params(&self) -> Vec<(String, String)> {
+        self.query().map(parse_query).unwrap_or_default()
+    }
+
+    /// The first value of the query parameter `name`.
+    fn query_param(&self, name: &str) -> Option<String> {
+        self.query_params()
+            .into_iter()
+            .find(|(param, _value)| param == name)
+            .map(|(_param, value)| value)
+    }
+}
+
+/// Splits a path into the path itself and its query string.
+fn split_query(path: &str) -> (&str, Option<&str>) {
+    match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    }
+}
+
+/// Decodes the parameters of a query string, as forms encode them.
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.split_once('=') {
+            Some((name, value)) => (decode(name), decode(value)),
+            None => (decode(param), String::new()),
+        })
+        .collect()
+}
+
+fn decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let byte = component
+                    .get(idx + 1..idx + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match byte {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        idx += 2;
+                    }
+                    // Malformed escapes are kept as they are
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        idx += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_query, split_query};
+
+    #[test]
+    fn parses_queries() {
+        assert_eq!(split_query("/search"), ("/search", None));
+        assert_eq!(
+            split_query("/search?q=a&page=2"),
+            ("/search", Some("q=a&page=2"))
+        );
+
+        let params = parse_query("q=hello+world&tag=a%26b&empty&bad=%zz&&caf%C3%A9=1");
+        assert_eq!(
+            params,
+            vec![
+                ("q".to_string(), "hello world".to_string()),
+                ("tag".to_string(), "a&b".to_string()),
+                ("empty".to_string(), "".to_string()),
+                ("bad".to_string(), "%zz".to_string()),
+                ("café".to_string(), "1".to_string()),
+            ]
+        );
+    }
+}
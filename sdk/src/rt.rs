@@ -0,0 +1,54 @@
+//! Runs handlers for the entry points the attribute macros generate.
+use std::fmt::Display;
+
+use proxysaur_bindings::log::{self, LogLevel};
+
+use crate::{Action, PreRequest, Request, RequestOutcome, Response};
+
+/// Logs the error and exits, so the proxy's failure policy decides what happens to the request.
+fn fail(hook: &str, err: impl Display) -> ! {
+    log::log(LogLevel::Error, &err.to_string(), &[("hook", hook)]);
+    std::process::exit(1)
+}
+
+pub fn run_request<O, E>(handler: impl FnOnce(Request) -> Result<O, E>)
+where
+    O: Into<RequestOutcome>,
+    E: Display,
+{
+    let request = Request::get().unwrap_or_else(|err| fail("request", err));
+    let result = match handler(request).map(Into::into) {
+        Ok(RequestOutcome::Forward(request)) => {
+            request.set();
+            Ok(())
+        }
+        Ok(RequestOutcome::Respond(response)) => response.respond(),
+        Err(err) => fail("request", err),
+    };
+    if let Err(err) = result {
+        fail("request", err);
+    }
+}
+
+pub fn run_response<E: Display>(handler: impl FnOnce(Response) -> Result<Response, E>) {
+    let response = Response::get().unwrap_or_else(|err| fail("response", err));
+    match handler(response) {
+        Ok(response) => {
+            if let Err(err) = response.set() {
+                fail("response", err);
+            }
+        }
+        Err(err) => fail("response", err),
+    }
+}
+
+pub fn run_pre_request<E: Display>(handler: impl FnOnce(PreRequest) -> Result<Action, E>) {
+    match handler(PreRequest::get()) {
+        Ok(action) => {
+            if let Err(err) = action.set() {
+                fail("pre-request", err);
+            }
+        }
+        Err(err) => fail("pre-request", err),
+    }
+}
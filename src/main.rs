@@ -1,9 +1,12 @@
 use anyhow::Result;
 use bytes::Bytes;
 use ca::init_project_dirs;
-use config::{Args, Config, Protocol, Proxy};
+use config::{
+    Args, Config, HookCapabilities, HookScripts, ModuleLimits, Protocol, Proxy, StateOptions,
+};
 
 mod proxy;
+mod test_module;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -28,7 +31,7 @@ async fn main() -> Result<()> {
                     path
                 }
             };
-            eprintln!("Go to the docs page to see how to trust this CA: https://proxysaur.us/ca#trusting-the-root-certificate-in-your-browser");
+            eprintln!("{}", ca::cli::ca_instructions(&res));
             println!("{}", path);
             return Ok(());
         }
@@ -61,7 +64,10 @@ async fn main() -> Result<()> {
             let mut config = Config::try_from(config_path.as_path())?;
 
             let ca_path = match ca::cli::generate_ca(config.ca_path.clone(), false).await {
-                Ok(ca_path) => ca_path,
+                Ok(ca_path) => {
+                    eprintln!("{}", ca::cli::ca_instructions(&ca_path));
+                    ca_path
+                }
                 Err(err) => {
                     eprintln!("Error generating or reading certificate authority: {err}");
                     return Err(err);
@@ -93,6 +99,7 @@ async fn main() -> Result<()> {
                     }
                 };
                 let proxy = Proxy {
+                    name: None,
                     pre_request_wasi_module_path: None,
                     request_wasi_module_path: None,
                     response_wasi_module_path: None,
@@ -104,6 +111,14 @@ async fn main() -> Result<()> {
                     address: "localhost".into(),
                     upstream_address: "".into(),
                     upstream_port: 9999,
+                    module_limits: ModuleLimits::default(),
+                    state: StateOptions::default(),
+                    capabilities: HookCapabilities::default(),
+                    pre_request_modules: vec![],
+                    request_modules: vec![],
+                    response_modules: vec![],
+                    scripts: HookScripts::default(),
+                    diff_hooks: false,
                 };
 
                 config.add_proxy(proxy);
@@ -112,6 +127,14 @@ async fn main() -> Result<()> {
             proxy::run(config).await?;
             return Ok(());
         }
+        Some(config::Commands::TestModule {
+            module,
+            hook,
+            config_path,
+            fixtures,
+        }) => {
+            return test_module::test_module(module, hook, config_path, fixtures).await;
+        }
         None => {}
     };
 
@@ -7,15 +7,17 @@ use anyhow::Result;
 use bytes::Bytes;
 use futures::future::{join_all, try_join_all};
 use notify::{watcher, RecursiveMode, Watcher};
-use protocols::http::proxy::{http_forward, http_proxy, HttpContext};
-use protocols::tcp::tunnel;
+use protocols::http::interceptor::WasmInterceptor;
+use protocols::http::proxy::HttpContext;
+use protocols::http::state::StateStore;
+use protocols::server::serve_connection;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::RwLock;
 use wasi_runtime::WasiRuntime;
 
-use config::{Config, Protocol, Proxy};
+use config::{Config, Protocol, Proxy, WasiModule};
 
-async fn cache_dir() -> Result<(PathBuf, PathBuf)> {
+pub(crate) async fn cache_dir() -> Result<(PathBuf, PathBuf)> {
     let project_dirs = directories::ProjectDirs::from("com", "proxysaur", "proxysaur")
         .ok_or_else(|| anyhow::Error::msg("Could not build project dirs"))?;
     let cache_dir = project_dirs.cache_dir();
@@ -96,10 +98,18 @@ pub async fn run(mut config: Config) -> Result<()> {
     let http_context = HttpContext::new(ca_path.as_path()).await?;
     let wasi_runtime = WasiRuntime::new(module_cache_dir)?;
 
+    let mut contexts = Vec::with_capacity(listeners.len());
+    for (_listener, proxy) in listeners.iter() {
+        // Each proxy's modules share state with each other, but not with other proxies
+        let state = StateStore::open(proxy.state.persist_path.clone()).await?;
+        contexts.push(http_context.clone().with_state(state));
+    }
+
     let _handle = join_all(
         listeners
             .into_iter()
-            .map(|(listener, proxy)| (listener, proxy, wasi_runtime.clone(), http_context.clone()))
+            .zip(contexts)
+            .map(|((listener, proxy), context)| (listener, proxy, wasi_runtime.clone(), context))
             .map(|(listener, proxy, wasi_runtime, context)| async move {
                 listen(listener, proxy, wasi_runtime, context).await
             }),
@@ -110,16 +120,13 @@ pub async fn run(mut config: Config) -> Result<()> {
 }
 
 async fn proxy_conn(
-    mut socket: TcpStream,
+    socket: TcpStream,
     proxy: Proxy,
     wasi_runtime: WasiRuntime,
     context: HttpContext,
 ) -> Result<()> {
-    match proxy.protocol {
-        Protocol::Tcp => tunnel(&mut socket, &proxy.upstream_address()).await,
-        Protocol::HttpForward => http_forward(socket, proxy, wasi_runtime, context).await,
-        Protocol::Http => http_proxy(socket, proxy, wasi_runtime, context).await,
-    }
+    let interceptor = Arc::new(WasmInterceptor::new(wasi_runtime));
+    serve_connection(socket, proxy, interceptor, context).await
 }
 
 async fn listen(
@@ -128,35 +135,59 @@ async fn listen(
     wasi_runtime: WasiRuntime,
     context: HttpContext,
 ) {
-    let config_path = proxy.proxy_configuration_path.clone();
+    let modules: Vec<WasiModule> = proxy
+        .pre_request_pipeline()
+        .into_iter()
+        .chain(proxy.request_pipeline())
+        .chain(proxy.response_pipeline())
+        .collect();
+    let config_paths: Vec<PathBuf> = proxy
+        .proxy_configuration_path
+        .iter()
+        .chain(modules.iter().flat_map(|module| &module.configuration_path))
+        .cloned()
+        .chain(proxy.script_paths())
+        .collect();
+    let module_paths: Vec<PathBuf> = modules.into_iter().map(|module| module.path).collect();
     let proxy = Arc::new(RwLock::new(proxy));
     let proxy_ = proxy.clone();
-
-    if let Some(config_path) = config_path {
+    let wasi_runtime_ = wasi_runtime.clone();
+    let handle = tokio::runtime::Handle::current();
+
+    let mut watch_dirs: Vec<PathBuf> = config_paths
+        .iter()
+        .chain(module_paths.iter())
+        .flat_map(|path| path.parent().map(Path::to_path_buf))
+        .collect();
+    watch_dirs.sort();
+    watch_dirs.dedup();
+
+    if !watch_dirs.is_empty() {
         tokio::task::spawn_blocking(move || {
             let (tx, rx) = channel();
             let mut watcher = watcher(tx, Duration::from_secs(1)).unwrap();
-            let watch_path = config_path.parent().unwrap();
-            tracing::info!(?watch_path, "Watching path");
-            watcher
-                .watch(watch_path, RecursiveMode::NonRecursive)
-                .unwrap();
+            for watch_path in watch_dirs.iter() {
+                tracing::info!(?watch_path, "Watching path");
+                watcher
+                    .watch(watch_path, RecursiveMode::NonRecursive)
+                    .unwrap();
+            }
 
             loop {
                 if let Ok(event) = rx.recv_timeout(Duration::from_secs(1)) {
                     match event {
+                        // Editors and build tools often save by renaming a new file into place
                         notify::DebouncedEvent::Write(path)
-                        | notify::DebouncedEvent::Create(path) => {
-                            if path == config_path {
-                                tracing::info!("Configuration changed. Updating..");
-                                if let Ok(new_contents) = std::fs::read(path) {
-                                    let wasi_configuration_bytes = Bytes::from(new_contents);
-                                    {
-                                        let mut proxy = proxy_.blocking_write();
-                                        proxy.wasi_configuration_bytes =
-                                            Some(wasi_configuration_bytes);
-                                    }
+                        | notify::DebouncedEvent::Create(path)
+                        | notify::DebouncedEvent::Rename(_, path) => {
+                            if config_paths.contains(&path) {
+                                tracing::info!(?path, "Configuration changed. Updating..");
+                                if let Ok(new_contents) = std::fs::read(&path) {
+                                    let mut proxy = proxy_.blocking_write();
+                                    proxy.update_configuration(&path, Bytes::from(new_contents));
                                 }
+                            } else if module_paths.contains(&path) {
+                                handle.block_on(wasi_runtime_.invalidate(&path));
                             }
                         }
                         event => tracing::info!(?event, "Received event"),
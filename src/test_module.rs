@@ -0,0 +1,714 @@
+//! Runs a module against fixtures for `proxysaur test-module`, so modules can be tested without
+//! running a proxy.
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use config::{Hook, Proxy};
+use http::{Method, Request, Response, StatusCode, Uri, Version};
+use hyper::Body;
+use protocols::http::{
+    diff::line_diff,
+    interceptor::{Connection, PreRequestAction, RequestLine, RequestOutcome},
+    runner::ModuleRunner,
+};
+use serde::{Deserialize, Deserializer};
+use wasi_runtime::WasiRuntime;
+
+use crate::proxy::cache_dir;
+
+fn default_method() -> String {
+    "GET".into()
+}
+
+fn default_uri() -> String {
+    "http://localhost/".into()
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HeaderValues {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Headers are written as a map, where a header sent more than once has a list of values.
+fn deserialize_headers<'de, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let headers: BTreeMap<String, HeaderValues> = BTreeMap::deserialize(deserializer)?;
+    let headers = headers
+        .into_iter()
+        .flat_map(|(name, values)| {
+            let values = match values {
+                HeaderValues::One(value) => vec![value],
+                HeaderValues::Many(values) => values,
+            };
+            values.into_iter().map(move |value| (name.clone(), value))
+        })
+        .collect();
+    Ok(headers)
+}
+
+fn deserialize_body<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(String::deserialize(deserializer)?.into_bytes())
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+struct FixtureRequest {
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default = "default_uri")]
+    uri: String,
+    #[serde(default, deserialize_with = "deserialize_headers")]
+    headers: Vec<(String, String)>,
+    #[serde(default, deserialize_with = "deserialize_body")]
+    body: Vec<u8>,
+}
+
+impl Default for FixtureRequest {
+    fn default() -> Self {
+        Self {
+            method: default_method(),
+            uri: default_uri(),
+            headers: vec![],
+            body: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+struct FixtureResponse {
+    #[serde(default = "default_status")]
+    status: u16,
+    #[serde(default, deserialize_with = "deserialize_headers")]
+    headers: Vec<(String, String)>,
+    #[serde(default, deserialize_with = "deserialize_body")]
+    body: Vec<u8>,
+}
+
+/// What a fixture should come out as. Only what's given is checked.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+struct Expectations {
+    /// `intercept`, `pass`, `block`, `tunnel-to` or `fail` for pre-request modules, and
+    /// `forward` or `respond` for request modules
+    action: Option<String>,
+    /// The status of the response, or of the block
+    status: Option<u16>,
+    tunnel_to: Option<String>,
+    method: Option<String>,
+    uri: Option<String>,
+    /// Headers that must be there with these values
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+}
+
+/// A request or response to run a module on. Pre-request and request modules get the request,
+/// and response modules get the response, answering the request.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+struct Fixture {
+    name: Option<String>,
+    request: Option<FixtureRequest>,
+    response: Option<FixtureResponse>,
+    expect: Option<Expectations>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Fixtures {
+    Many(Vec<Fixture>),
+    One(Fixture),
+}
+
+#[derive(Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize)]
+struct HarEntry {
+    request: HarRequest,
+    response: HarResponse,
+}
+
+#[derive(Deserialize)]
+struct HarHeader {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct HarRequest {
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    #[serde(rename = "postData")]
+    post_data: Option<HarContent>,
+}
+
+#[derive(Deserialize)]
+struct HarResponse {
+    status: u16,
+    #[serde(default)]
+    headers: Vec<HarHeader>,
+    content: Option<HarContent>,
+}
+
+#[derive(Deserialize)]
+struct HarContent {
+    #[serde(default)]
+    text: String,
+    /// `base64` for binary content
+    encoding: Option<String>,
+}
+
+impl HarContent {
+    fn into_body(self) -> Result<Vec<u8>> {
+        match self.encoding.as_deref() {
+            Some("base64") => Ok(base64::decode(self.text)?),
+            Some(encoding) => Err(anyhow::Error::msg(format!(
+                "Unsupported HAR content encoding: {encoding}"
+            ))),
+            None => Ok(self.text.into_bytes()),
+        }
+    }
+}
+
+/// HTTP/2 pseudo-headers, like `:authority`, aren't headers as far as modules are concerned.
+fn har_headers(headers: Vec<HarHeader>) -> Vec<(String, String)> {
+    headers
+        .into_iter()
+        .filter(|header| !header.name.starts_with(':'))
+        .map(|header| (header.name.to_lowercase(), header.value))
+        .collect()
+}
+
+fn har_fixtures(contents: &[u8]) -> Result<Vec<Fixture>> {
+    let har: Har = serde_json::from_slice(contents)?;
+    har.log
+        .entries
+        .into_iter()
+        .map(|entry| {
+            Ok(Fixture {
+                name: Some(format!("{} {}", entry.request.method, entry.request.url)),
+                request: Some(FixtureRequest {
+                    method: entry.request.method,
+                    uri: entry.request.url,
+                    headers: har_headers(entry.request.headers),
+                    body: match entry.request.post_data {
+                        Some(data) => data.into_body()?,
+                        None => vec![],
+                    },
+                }),
+                response: Some(FixtureResponse {
+                    status: entry.response.status,
+                    headers: har_headers(entry.response.headers),
+                    body: match entry.response.content {
+                        Some(content) => content.into_body()?,
+                        None => vec![],
+                    },
+                }),
+                expect: None,
+            })
+        })
+        .collect()
+}
+
+/// Reads the fixtures in `path`, going by its extension.
+fn load_fixtures(path: &Path) -> Result<Vec<Fixture>> {
+    let contents = std::fs::read(path)?;
+    let fixtures = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("har") => return har_fixtures(&contents),
+        Some("json") => serde_json::from_slice(&contents)?,
+        _ => serde_yaml::from_slice(&contents)?,
+    };
+    match fixtures {
+        Fixtures::Many(fixtures) => Ok(fixtures),
+        Fixtures::One(fixture) => Ok(vec![fixture]),
+    }
+}
+
+/// A request, response or pre-request decision, in a form that can be printed, diffed and
+/// checked against expectations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Rendered {
+    action: Option<String>,
+    /// Why a module failed
+    reason: Option<String>,
+    status: Option<u16>,
+    tunnel_to: Option<String>,
+    method: Option<String>,
+    uri: Option<String>,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+}
+
+impl Rendered {
+    fn from_fixture_request(request: &FixtureRequest) -> Self {
+        Self {
+            method: Some(request.method.clone()),
+            uri: Some(request.uri.clone()),
+            headers: fixture_headers(&request.headers),
+            body: Some(render_body(&request.body)),
+            ..Self::default()
+        }
+    }
+
+    fn from_fixture_response(response: &FixtureResponse) -> Self {
+        Self {
+            status: Some(response.status),
+            headers: fixture_headers(&response.headers),
+            body: Some(render_body(&response.body)),
+            ..Self::default()
+        }
+    }
+
+    fn from_action(action: &PreRequestAction) -> Self {
+        let reason = match action {
+            PreRequestAction::Fail(reason) => Some(reason.clone()),
+            _ => None,
+        };
+        let (action, status, tunnel_to) = match action {
+            PreRequestAction::Intercept => ("intercept", None, None),
+            PreRequestAction::Pass => ("pass", None, None),
+            PreRequestAction::Block(status) => ("block", Some(status.as_u16()), None),
+            PreRequestAction::TunnelTo(hostname) => {
+                ("tunnel-to", None, Some(hostname.authority.clone()))
+            }
+            PreRequestAction::Fail(_) => ("fail", None, None),
+        };
+        Self {
+            action: Some(action.to_string()),
+            reason,
+            status,
+            tunnel_to,
+            ..Self::default()
+        }
+    }
+
+    async fn from_request(request: Request<Body>) -> Result<Self> {
+        let (parts, body) = request.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        Ok(Self {
+            action: Some("forward".into()),
+            method: Some(parts.method.to_string()),
+            uri: Some(parts.uri.to_string()),
+            headers: sorted_headers(&parts.headers),
+            body: Some(render_body(&body)),
+            ..Self::default()
+        })
+    }
+
+    async fn from_response(response: Response<Body>) -> Result<Self> {
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        Ok(Self {
+            status: Some(parts.status.as_u16()),
+            headers: sorted_headers(&parts.headers),
+            body: Some(render_body(&body)),
+            ..Self::default()
+        })
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+        if let Some(action) = self.action.as_ref() {
+            lines.push(format!("action: {action}"));
+        }
+        if let Some(reason) = self.reason.as_ref() {
+            lines.push(format!("reason: {reason}"));
+        }
+        if let Some(tunnel_to) = self.tunnel_to.as_ref() {
+            lines.push(format!("tunnel to: {tunnel_to}"));
+        }
+        match (self.method.as_ref(), self.uri.as_ref(), self.status) {
+            (Some(method), Some(uri), _) => lines.push(format!("{method} {uri}")),
+            (_, _, Some(status)) => lines.push(format!("status: {status}")),
+            _ => {}
+        }
+        for (name, value) in self.headers.iter() {
+            lines.push(format!("{name}: {value}"));
+        }
+        if let Some(body) = self.body.as_ref() {
+            lines.push(String::new());
+            lines.extend(body.lines().map(String::from));
+        }
+        lines
+    }
+
+    /// Describes everything that doesn't match what's expected.
+    fn mismatches(&self, expect: &Expectations) -> Vec<String> {
+        let mut mismatches = vec![];
+        let mut check = |what: &str, expected: Option<String>, actual: Option<String>| {
+            if let Some(expected) = expected {
+                if Some(&expected) != actual.as_ref() {
+                    mismatches.push(format!("expected {what} {expected:?}, got {actual:?}"));
+                }
+            }
+        };
+        check("action", expect.action.clone(), self.action.clone());
+        check(
+            "status",
+            expect.status.map(|status| status.to_string()),
+            self.status.map(|status| status.to_string()),
+        );
+        check(
+            "tunnel to",
+            expect.tunnel_to.clone(),
+            self.tunnel_to.clone(),
+        );
+        check("method", expect.method.clone(), self.method.clone());
+        check("uri", expect.uri.clone(), self.uri.clone());
+        check("body", expect.body.clone(), self.body.clone());
+        for (name, expected) in expect.headers.iter() {
+            let actual = self
+                .headers
+                .iter()
+                .find(|(header, _value)| header.eq_ignore_ascii_case(name))
+                .map(|(_header, value)| value.clone());
+            check(&format!("header {name}"), Some(expected.clone()), actual);
+        }
+        mismatches
+    }
+}
+
+/// Bodies are shown as text, unless they aren't text at all.
+fn render_body(body: &[u8]) -> String {
+    match std::str::from_utf8(body) {
+        Ok(body) => body.to_string(),
+        Err(_) => format!("<{} bytes of binary data>", body.len()),
+    }
+}
+
+/// Header names the way hyper gives them, so fixtures can use any case without showing up in
+/// diffs. Repeated headers keep their order.
+fn fixture_headers(headers: &[(String, String)]) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.clone()))
+        .collect();
+    headers.sort_by(|(a, _), (b, _)| a.cmp(b));
+    headers
+}
+
+fn sorted_headers(headers: &http::HeaderMap) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.to_string(), value)
+        })
+        .collect();
+    headers.sort_by(|(a, _), (b, _)| a.cmp(b));
+    headers
+}
+
+fn build_request(request: &FixtureRequest) -> Result<Request<Body>> {
+    let mut builder = Request::builder()
+        .method(Method::from_bytes(request.method.as_bytes())?)
+        .uri(request.uri.as_str())
+        .version(Version::HTTP_11);
+    for (name, value) in request.headers.iter() {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    Ok(builder.body(Body::from(request.body.clone()))?)
+}
+
+fn build_response(response: &FixtureResponse) -> Result<Response<Body>> {
+    let mut builder = Response::builder().status(StatusCode::from_u16(response.status)?);
+    for (name, value) in response.headers.iter() {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    Ok(builder.body(Body::from(response.body.clone()))?)
+}
+
+/// Runs the fixture through the module, returning what went in and what came out.
+async fn run_fixture(
+    runner: &mut ModuleRunner,
+    hook: Hook,
+    fixture: &Fixture,
+) -> Result<(Rendered, Rendered)> {
+    let request = fixture.request.clone().unwrap_or_default();
+    match hook {
+        Hook::PreRequest => {
+            let connection = Connection {
+                client_address: "127.0.0.1:0".parse()?,
+                listener_name: "test-module".into(),
+                listener_port: 0,
+            };
+            let action = runner
+                .pre_request(&build_request(&request)?, &connection)
+                .await?;
+            Ok((
+                Rendered::from_fixture_request(&request),
+                Rendered::from_action(&action),
+            ))
+        }
+        Hook::Request => {
+            let input = Rendered::from_fixture_request(&request);
+            let output = match runner.request(build_request(&request)?).await? {
+                RequestOutcome::Forward(request) => Rendered::from_request(request).await?,
+                RequestOutcome::Respond(_request, response) => Rendered {
+                    action: Some("respond".into()),
+                    ..Rendered::from_response(response).await?
+                },
+            };
+            Ok((input, output))
+        }
+        Hook::Response => {
+            let response = fixture
+                .response
+                .as_ref()
+                .ok_or_else(|| anyhow::Error::msg("Response fixtures need a response"))?;
+            let request_line = RequestLine {
+                method: Method::from_bytes(request.method.as_bytes())?,
+                uri: request.uri.parse::<Uri>()?,
+                version: Version::HTTP_11,
+            };
+            let output = runner
+                .response(build_response(response)?, request_line)
+                .await?;
+            Ok((
+                Rendered::from_fixture_response(response),
+                Rendered::from_response(output).await?,
+            ))
+        }
+    }
+}
+
+/// Runs the module at `module` against every fixture, printing what it did to each. Fails if
+/// any fixture doesn't come out as expected, or can't be run.
+pub async fn test_module(
+    module: PathBuf,
+    hook: Hook,
+    config_path: Option<PathBuf>,
+    fixture_paths: Vec<PathBuf>,
+) -> Result<()> {
+    let mut proxy = Proxy::new();
+    match hook {
+        Hook::PreRequest => proxy.pre_request_wasi_module_path = Some(module),
+        Hook::Request => proxy.request_wasi_module_path = Some(module),
+        Hook::Response => proxy.response_wasi_module_path = Some(module),
+    }
+    proxy.proxy_configuration_path = config_path;
+    proxy.load_configuration()?;
+
+    let (_cache_dir, module_cache_dir) = cache_dir().await?;
+    let mut runner = ModuleRunner::new(WasiRuntime::new(module_cache_dir)?, proxy);
+    run_fixtures(&mut runner, hook, &fixture_paths).await
+}
+
+async fn run_fixtures(
+    runner: &mut ModuleRunner,
+    hook: Hook,
+    fixture_paths: &[PathBuf],
+) -> Result<()> {
+    let mut total = 0;
+    let mut failures = 0;
+    for path in fixture_paths.iter() {
+        for (idx, fixture) in load_fixtures(path)?.iter().enumerate() {
+            total += 1;
+            let name = fixture
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("{}#{}", path.display(), idx + 1));
+            let mut report = String::new();
+            let failed = match run_fixture(runner, hook, fixture).await {
+                Ok((input, output)) => {
+                    match hook {
+                        Hook::PreRequest => {
+                            for line in output.lines() {
+                                let _res = writeln!(report, "  {line}");
+                            }
+                        }
+                        Hook::Request | Hook::Response => {
+                            for line in line_diff(&input.lines(), &output.lines()) {
+                                let _res = writeln!(report, "{line}");
+                            }
+                        }
+                    }
+                    let mismatches = fixture
+                        .expect
+                        .as_ref()
+                        .map(|expect| output.mismatches(expect))
+                        .unwrap_or_default();
+                    for mismatch in mismatches.iter() {
+                        let _res = writeln!(report, "! {mismatch}");
+                    }
+                    !mismatches.is_empty()
+                }
+                Err(err) => {
+                    let _res = writeln!(report, "! {err:#}");
+                    true
+                }
+            };
+            if failed {
+                failures += 1;
+            }
+            println!("{} {name}", if failed { "FAIL" } else { "PASS" });
+            print!("{report}");
+        }
+    }
+
+    println!("{} of {total} fixtures passed", total - failures);
+    if failures > 0 {
+        let msg = format!("{failures} of {total} fixtures failed");
+        return Err(anyhow::Error::msg(msg));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use config::{Hook, Proxy};
+    use protocols::http::runner::ModuleRunner;
+    use tempdir::TempDir;
+    use wasi_runtime::WasiRuntime;
+
+    use super::{har_fixtures, load_fixtures, run_fixtures, Expectations, Rendered};
+
+    #[test]
+    fn reads_har_entries() {
+        let har = br#"{"log": {"entries": [{
+            "request": {
+                "method": "POST",
+                "url": "https://example.com/api",
+                "headers": [{"name": ":authority", "value": "example.com"},
+                            {"name": "Content-Type", "value": "application/json"}],
+                "postData": {"mimeType": "application/json", "text": "{}"}
+            },
+            "response": {"status": 201, "headers": [], "content": {"size": 2, "text": "ok"}}
+        }]}}"#;
+        let fixtures = har_fixtures(har).expect("should read the HAR");
+        assert_eq!(fixtures.len(), 1);
+        let request = fixtures[0].request.as_ref().expect("should have a request");
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.body, b"{}");
+        assert_eq!(
+            request.headers,
+            vec![("content-type".to_string(), "application/json".to_string())]
+        );
+        let response = fixtures[0]
+            .response
+            .as_ref()
+            .expect("should have a response");
+        assert_eq!(response.status, 201);
+        assert_eq!(response.body, b"ok");
+    }
+
+    #[test]
+    fn reads_binary_har_content() {
+        let har = br#"{"log": {"entries": [{
+            "request": {"method": "GET", "url": "https://example.com/logo.png"},
+            "response": {"status": 200, "content": {"text": "iVBORw==", "encoding": "base64"}}
+        }]}}"#;
+        let fixtures = har_fixtures(har).expect("should read the HAR");
+        let response = fixtures[0]
+            .response
+            .as_ref()
+            .expect("should have a response");
+        assert_eq!(response.body, b"\x89PNG");
+    }
+
+    #[test]
+    fn keeps_repeated_headers() {
+        let dir = TempDir::new("proxysaur-fixtures").expect("should create a temp dir");
+        let path = dir.path().join("fixtures.yaml");
+        std::fs::write(
+            &path,
+            "response:\n  headers:\n    Set-Cookie: [a=1, b=2]\n    Content-Type: text/plain",
+        )
+        .expect("should write the fixtures");
+        let fixtures = load_fixtures(&path).expect("should load the fixtures");
+        let response = fixtures[0]
+            .response
+            .as_ref()
+            .expect("should have a response");
+        assert_eq!(
+            response.headers,
+            vec![
+                ("Content-Type".to_string(), "text/plain".to_string()),
+                ("Set-Cookie".to_string(), "a=1".to_string()),
+                ("Set-Cookie".to_string(), "b=2".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_a_module_against_fixtures() {
+        let dir = TempDir::new("proxysaur-test-module").expect("should create a temp dir");
+        let module = dir.path().join("intercept.wat");
+        std::fs::write(
+            &module,
+            r#"(module
+                (import "pre-request" "http-set-proxy-mode" (func $set_proxy_mode (param i32)))
+                (func (export "_start") (call $set_proxy_mode (i32.const 0))))"#,
+        )
+        .expect("should write the module");
+        let fixtures = dir.path().join("fixtures.yaml");
+        std::fs::write(
+            &fixtures,
+            r#"
+- name: intercepted
+  request:
+    uri: https://example.com/
+  expect:
+    action: intercept
+- name: passed
+  expect:
+    action: pass
+"#,
+        )
+        .expect("should write the fixtures");
+
+        let mut proxy = Proxy::new();
+        proxy.pre_request_wasi_module_path = Some(module);
+        let wasi_runtime =
+            WasiRuntime::new(dir.path().to_path_buf()).expect("should build the runtime");
+        let mut runner = ModuleRunner::new(wasi_runtime, proxy);
+        let err = run_fixtures(&mut runner, Hook::PreRequest, &[fixtures])
+            .await
+            .expect_err("should fail the fixture expecting a pass");
+        assert_eq!(err.to_string(), "1 of 2 fixtures failed");
+    }
+
+    #[test]
+    fn checks_expectations() {
+        let output = Rendered {
+            status: Some(418),
+            headers: vec![("content-type".into(), "text/plain".into())],
+            body: Some("mocked!".into()),
+            ..Rendered::default()
+        };
+        let expect: Expectations = serde_yaml::from_str(
+            "status: 418\nheaders:\n  Content-Type: text/plain\nbody: mocked!",
+        )
+        .expect("should parse the expectations");
+        assert!(output.mismatches(&expect).is_empty());
+
+        let expect: Expectations =
+            serde_yaml::from_str("status: 200\nbody: mocked!").expect("should parse");
+        assert_eq!(
+            output.mismatches(&expect),
+            vec![r#"expected status "200", got Some("418")"#]
+        );
+    }
+}
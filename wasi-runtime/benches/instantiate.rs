@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+
+use config::ModuleLimits;
+use criterion::{criterion_group, criterion_main, Criterion};
+use wasi_runtime::{Config, Engine, Linker, Module, Store, WasiCtx, WasiCtxBuilder, WasiRuntime};
+
+/// A WASI command that does nothing, so only the per-request setup is measured.
+const MODULE: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")))
+"#;
+
+struct Context {
+    wasi: WasiCtx,
+}
+
+fn context() -> Context {
+    Context {
+        wasi: WasiCtxBuilder::new().build(),
+    }
+}
+
+/// Metered like the runtime's engine, so the comparison only measures linking and allocation.
+fn baseline_engine() -> Engine {
+    let mut config = Config::new();
+    config.consume_fuel(true).epoch_interruption(true);
+    Engine::new(&config).expect("should build the engine")
+}
+
+/// How every hook ran before modules were pre-linked: a new linker per request, and the
+/// default on-demand instance allocator.
+fn link_and_instantiate(engine: &Engine, module: &Module) {
+    let mut linker: Linker<Context> = Linker::new(engine);
+    wasi_runtime::add_to_linker(&mut linker, |ctx| &mut ctx.wasi).expect("should link WASI");
+    let mut store = Store::new(engine, context());
+    store.add_fuel(u64::MAX).expect("should add fuel");
+    store.set_epoch_deadline(u64::MAX / 2);
+    linker
+        .module(&mut store, "", module)
+        .expect("should add the module");
+    linker
+        .get_default(&mut store, "")
+        .expect("should find the entry point")
+        .typed::<(), (), _>(&store)
+        .expect("should type the entry point")
+        .call(&mut store, ())
+        .expect("should call the module");
+}
+
+async fn run_pre_linked(mut runtime: WasiRuntime, path: &Path) {
+    let limits = ModuleLimits::default();
+    let mut store = Store::new(&runtime.engine, context());
+    runtime
+        .apply_limits(&mut store, &limits)
+        .expect("should apply the limits");
+    let instance_pre = runtime
+        .instance_pre(&mut store, path, |linker| {
+            wasi_runtime::add_to_linker(linker, |ctx: &mut Context| &mut ctx.wasi)?;
+            Ok(())
+        })
+        .await
+        .expect("should link the module");
+    let (result, _) = runtime
+        .run_module(instance_pre, store, &limits)
+        .await
+        .expect("should run the module");
+    result.expect("should call the module");
+}
+
+fn module_path() -> PathBuf {
+    let path = std::env::temp_dir().join("proxysaur-bench-module.wat");
+    std::fs::write(&path, MODULE).expect("should write the module");
+    path
+}
+
+fn per_request_overhead(c: &mut Criterion) {
+    let path = module_path();
+    let tokio_runtime = tokio::runtime::Runtime::new().expect("should build the tokio runtime");
+    let mut group = c.benchmark_group("per-request overhead");
+
+    let engine = baseline_engine();
+    let module = Module::from_file(&engine, &path).expect("should compile the module");
+    group.bench_function("link and instantiate", |b| {
+        b.iter(|| link_and_instantiate(&engine, &module))
+    });
+
+    let runtime = WasiRuntime::new(std::env::temp_dir()).expect("should build the runtime");
+    group.bench_function("pre-linked and pooled", |b| {
+        b.to_async(&tokio_runtime)
+            .iter(|| run_pre_linked(runtime.clone(), &path))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, per_request_overhead);
+criterion_main!(benches);
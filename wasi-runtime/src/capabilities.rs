@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use config::{DirAccess, ModuleCapabilities};
+use wasi_common::{DirCaps, FileCaps};
+use wasmtime_wasi::sync::{ambient_authority, dir::Dir, Dir as AmbientDir};
+use wasmtime_wasi::{WasiCtx, WasiCtxBuilder};
+
+/// Descriptors 0 to 2 are stdio, preopened directories follow them.
+const FIRST_PREOPEN_FD: u32 = 3;
+
+fn read_only_caps() -> (DirCaps, FileCaps) {
+    let dir_caps = DirCaps::OPEN
+        | DirCaps::READDIR
+        | DirCaps::READLINK
+        | DirCaps::PATH_FILESTAT_GET
+        | DirCaps::FILESTAT_GET;
+    let file_caps = FileCaps::READ
+        | FileCaps::SEEK
+        | FileCaps::TELL
+        | FileCaps::ADVISE
+        | FileCaps::FILESTAT_GET
+        | FileCaps::POLL_READWRITE;
+    (dir_caps, file_caps)
+}
+
+/// Builds the WASI environment of the module at `module_path`, which only exposes what
+/// `capabilities` grants it.
+pub fn build_wasi_ctx(capabilities: &ModuleCapabilities, module_path: &Path) -> Result<WasiCtx> {
+    let name = module_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let args: Vec<String> = std::iter::once(name)
+        .chain(capabilities.args.iter().cloned())
+        .collect();
+    // Variables that aren't set are skipped rather than passed through empty
+    let envs: Vec<(String, String)> = capabilities
+        .env
+        .iter()
+        .filter_map(|name| std::env::var(name).ok().map(|value| (name.clone(), value)))
+        .collect();
+
+    let mut builder = WasiCtxBuilder::new().args(&args)?.envs(&envs)?;
+    if capabilities.stdio {
+        builder = builder.inherit_stdio();
+    }
+    let mut wasi = builder.build();
+
+    for (fd, preopen) in (FIRST_PREOPEN_FD..).zip(capabilities.preopened_dirs.iter()) {
+        let dir = match AmbientDir::open_ambient_dir(&preopen.host_path, ambient_authority()) {
+            Ok(dir) => dir,
+            Err(err) => {
+                tracing::error!(path = ?preopen.host_path, %err, "Error opening preopened directory.");
+                return Err(err.into());
+            }
+        };
+        let (dir_caps, file_caps) = match preopen.access {
+            DirAccess::ReadOnly => read_only_caps(),
+            DirAccess::ReadWrite => (DirCaps::all(), FileCaps::all()),
+        };
+        wasi.insert_dir(
+            fd,
+            Box::new(Dir::from_cap_std(dir)),
+            dir_caps,
+            file_caps,
+            PathBuf::from(&preopen.guest_path),
+        );
+    }
+
+    Ok(wasi)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use config::{DirAccess, ModuleCapabilities, ModuleLimits, PreopenedDir};
+    use tempdir::TempDir;
+    use wasmtime::Store;
+    use wasmtime_wasi::WasiCtx;
+
+    use super::build_wasi_ctx;
+    use crate::WasiRuntime;
+
+    /// Writes a module that traps unless it sees exactly `env` variables and `args` arguments.
+    fn counting_module(dir: &Path, env: u32, args: u32) -> PathBuf {
+        let path = dir.join("counting-module.wat");
+        let module = format!(
+            r#"(module
+                (import "wasi_snapshot_preview1" "environ_sizes_get"
+                    (func $environ_sizes_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "args_sizes_get"
+                    (func $args_sizes_get (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "_start")
+                    (drop (call $environ_sizes_get (i32.const 0) (i32.const 4)))
+                    (if (i32.ne (i32.load (i32.const 0)) (i32.const {env})) (then unreachable))
+                    (drop (call $args_sizes_get (i32.const 8) (i32.const 12)))
+                    (if (i32.ne (i32.load (i32.const 8)) (i32.const {args})) (then unreachable))))"#
+        );
+        std::fs::write(&path, module).expect("should write the module");
+        path
+    }
+
+    async fn run(
+        capabilities: &ModuleCapabilities,
+        dir: &TempDir,
+        path: PathBuf,
+    ) -> anyhow::Result<()> {
+        let mut runtime = WasiRuntime::new(dir.path().to_path_buf())?;
+        let wasi = build_wasi_ctx(capabilities, &path)?;
+        let mut store = Store::new(&runtime.engine, wasi);
+        let limits = ModuleLimits::default();
+        runtime.apply_limits(&mut store, &limits)?;
+        let instance_pre = runtime
+            .instance_pre(&mut store, &path, |linker| {
+                crate::add_to_linker(linker, |wasi: &mut WasiCtx| wasi)?;
+                Ok(())
+            })
+            .await?;
+        let (result, _wasi) = runtime.run_module(instance_pre, store, &limits).await?;
+        result
+    }
+
+    #[tokio::test]
+    async fn exposes_nothing_by_default() {
+        std::env::set_var("PROXYSAUR_CAPABILITIES_TEST", "set");
+        let dir = TempDir::new("proxysaur-capabilities").expect("should create a temp dir");
+        let path = counting_module(dir.path(), 0, 1);
+        run(&ModuleCapabilities::default(), &dir, path)
+            .await
+            .expect("should only see its name");
+    }
+
+    #[tokio::test]
+    async fn passes_allowed_env_and_args() {
+        std::env::set_var("PROXYSAUR_CAPABILITIES_TEST", "set");
+        let capabilities = ModuleCapabilities {
+            env: vec![
+                "PROXYSAUR_CAPABILITIES_TEST".into(),
+                "PROXYSAUR_CAPABILITIES_UNSET".into(),
+            ],
+            args: vec!["--verbose".into()],
+            ..ModuleCapabilities::default()
+        };
+        let dir = TempDir::new("proxysaur-capabilities").expect("should create a temp dir");
+        let path = counting_module(dir.path(), 1, 2);
+        run(&capabilities, &dir, path)
+            .await
+            .expect("should see the allowed variable and its arguments");
+    }
+
+    #[test]
+    fn fails_on_missing_directories() {
+        let capabilities = ModuleCapabilities {
+            preopened_dirs: vec![PreopenedDir {
+                host_path: PathBuf::from("/proxysaur/does/not/exist"),
+                guest_path: "/fixtures".into(),
+                access: DirAccess::ReadOnly,
+            }],
+            ..ModuleCapabilities::default()
+        };
+        let module_path = PathBuf::from("request.wasm");
+        assert!(build_wasi_ctx(&capabilities, &module_path).is_err());
+    }
+}
@@ -1,87 +1,393 @@
+mod capabilities;
+
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
-    hash::{Hash, Hasher},
+    any::{Any, TypeId},
+    collections::HashMap,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, Semaphore};
 
 use anyhow::Result;
-pub use wasmtime::{Config, Engine, Linker, Module, Store};
+pub use capabilities::build_wasi_ctx;
+use config::ModuleLimits;
+use sha2::{Digest, Sha256};
+pub use wasmtime::{Config, Engine, InstancePre, Linker, Module, Store, StoreLimits};
+use wasmtime::{
+    InstanceAllocationStrategy, InstanceLimits, PoolingAllocationStrategy, StoreLimitsBuilder,
+};
 pub use wasmtime_wasi::add_to_linker;
 pub use wasmtime_wasi::{WasiCtx, WasiCtxBuilder};
 
+/// How often the engine's epoch is incremented, which bounds the precision of timeouts.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Stores always have an epoch deadline, this one is far enough away to never be reached
+/// while staying clear of overflowing the engine's epoch counter.
+const NO_EPOCH_DEADLINE: u64 = u64::MAX / 2;
+
+/// Increments the engine's epoch on a background thread until dropped.
+struct EpochTicker {
+    stopped: Arc<AtomicBool>,
+}
+
+impl EpochTicker {
+    fn start(engine: Engine) -> Self {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_ = stopped.clone();
+        std::thread::spawn(move || {
+            while !stopped_.load(Ordering::Relaxed) {
+                std::thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        });
+        Self { stopped }
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Compiled modules, keyed by the digest of their contents.
+type ModuleCache = HashMap<String, Module>;
+
+/// Pre-linked modules, keyed by module path and the store data of the hook they're linked for.
+type InstanceCache = HashMap<(PathBuf, TypeId), Box<dyn Any + Send + Sync>>;
+
+/// Sizes the pooling allocator. Instances only live while a module slot is held, so the pool
+/// never needs more than one per slot. The per-instance limits are generous since the real
+/// limits are applied per proxy through `StoreLimits`.
+fn pooling_strategy(instances: u32) -> InstanceAllocationStrategy {
+    InstanceAllocationStrategy::Pooling {
+        strategy: PoolingAllocationStrategy::default(),
+        module_limits: wasmtime::ModuleLimits {
+            types: 10_000,
+            functions: 100_000,
+            globals: 10_000,
+            table_elements: 100_000,
+            memory_pages: 65_536,
+            ..wasmtime::ModuleLimits::default()
+        },
+        instance_limits: InstanceLimits { count: instances },
+    }
+}
+
+/// How many epoch ticks a module may run for.
+fn epoch_deadline(limits: &ModuleLimits) -> u64 {
+    match limits.timeout_ms {
+        Some(timeout_ms) => {
+            let tick = EPOCH_TICK.as_millis() as u64;
+            // The current tick may already be partially elapsed, so round up
+            timeout_ms / tick + 1
+        }
+        None => NO_EPOCH_DEADLINE,
+    }
+}
+
 #[derive(Clone)]
 pub struct WasiRuntime {
     pub engine: Engine,
-    module_cache: Arc<RwLock<HashMap<PathBuf, Module>>>,
+    module_cache: Arc<RwLock<ModuleCache>>,
+    /// The digest of each module path's contents when it was loaded.
+    module_digests: Arc<RwLock<HashMap<PathBuf, String>>>,
+    instance_cache: Arc<RwLock<InstanceCache>>,
     cache_dir: PathBuf,
+    /// Identifies the compiler and engine settings a compiled artifact is only valid for.
+    engine_fingerprint: String,
+    module_slots: Arc<Semaphore>,
+    _epoch_ticker: Arc<EpochTicker>,
 }
 
 impl WasiRuntime {
     pub fn new(cache_dir: PathBuf) -> Result<Self> {
-        let config = Config::new();
+        // Modules run on blocking threads so they can't stall the async workers, and at most
+        // one per core runs at a time so they can't starve other blocking work either
+        let module_slots = std::thread::available_parallelism()
+            .map(|slots| slots.get())
+            .unwrap_or(4);
+        let mut config = Config::new();
+        config
+            .consume_fuel(true)
+            .epoch_interruption(true)
+            .allocation_strategy(pooling_strategy(module_slots as u32));
+        let engine = Engine::new(&config)?;
+        let engine_fingerprint = engine_fingerprint(&engine)?;
         Ok(Self {
-            engine: Engine::new(&config)?,
+            _epoch_ticker: Arc::new(EpochTicker::start(engine.clone())),
+            engine,
             module_cache: Arc::new(RwLock::new(HashMap::new())),
+            module_digests: Arc::new(RwLock::new(HashMap::new())),
+            instance_cache: Arc::new(RwLock::new(HashMap::new())),
             cache_dir,
+            engine_fingerprint,
+            module_slots: Arc::new(Semaphore::new(module_slots)),
         })
     }
 
-    fn module_cache_path_for_path(&self, path: &Path) -> PathBuf {
-        let mut hasher = DefaultHasher::new();
-        path.hash(&mut hasher);
-        let value = hasher.finish();
-        let last_component = format!("{}.wasmtime", value);
+    /// Builds the memory and table limits for a store, which must be installed with
+    /// `Store::limiter`.
+    pub fn store_limits(limits: &ModuleLimits) -> StoreLimits {
+        let mut builder = StoreLimitsBuilder::new();
+        if let Some(max_memory_bytes) = limits.max_memory_bytes {
+            builder = builder.memory_size(max_memory_bytes);
+        }
+        if let Some(max_table_elements) = limits.max_table_elements {
+            builder = builder.table_elements(max_table_elements);
+        }
+        builder.build()
+    }
+
+    /// Gives the store its fuel. Its execution deadline is only set once it runs, see
+    /// `run_module`.
+    pub fn apply_limits<T>(&self, store: &mut Store<T>, limits: &ModuleLimits) -> Result<()> {
+        store.add_fuel(limits.fuel.unwrap_or(u64::MAX))?;
+        Ok(())
+    }
+
+    /// Fetches the module at `path`, pre-linked for a hook whose store holds a `T`. `link`
+    /// adds the hook's host functions, and only runs the first time the module is used by
+    /// that hook.
+    pub async fn instance_pre<T: Send + 'static>(
+        &mut self,
+        store: &mut Store<T>,
+        path: &Path,
+        link: impl FnOnce(&mut Linker<T>) -> Result<()>,
+    ) -> Result<InstancePre<T>> {
+        let key = (path.to_path_buf(), TypeId::of::<T>());
+        let instance_pre = {
+            let cache = self.instance_cache.read().await;
+            cache
+                .get(&key)
+                .and_then(|instance_pre| instance_pre.downcast_ref::<InstancePre<T>>())
+                .cloned()
+        };
+        if let Some(instance_pre) = instance_pre {
+            return Ok(instance_pre);
+        }
+
+        let module = self.fetch_module(path).await?;
+        let mut linker: Linker<T> = Linker::new(&self.engine);
+        link(&mut linker)?;
+        let instance_pre = linker.instantiate_pre(&mut *store, &module)?;
+        tracing::debug!(?path, "Linked module.");
+
+        let mut cache = self.instance_cache.write().await;
+        cache.insert(key, Box::new(instance_pre.clone()));
+        Ok(instance_pre)
+    }
+
+    /// Instantiates the module and calls its entry point on the blocking pool, handing back
+    /// the store's data along with the result of the call. Host functions run on the same
+    /// blocking thread, so they're free to wait on async work with `Handle::block_on`.
+    pub async fn run_module<T: Send + 'static>(
+        &self,
+        instance_pre: InstancePre<T>,
+        mut store: Store<T>,
+        limits: &ModuleLimits,
+    ) -> Result<(Result<()>, T)> {
+        let deadline = epoch_deadline(limits);
+        self.run_blocking(move || {
+            // Starts the timeout once the module has a slot, so waiting for one doesn't count
+            store.set_epoch_deadline(deadline);
+            let result = call_module(&instance_pre, &mut store);
+            (result, store.into_data())
+        })
+        .await
+    }
+
+    /// Runs `work` on the blocking pool in one of the module slots, for anything that runs
+    /// guest code other than modules, like scripts.
+    pub async fn run_blocking<R: Send + 'static>(
+        &self,
+        work: impl FnOnce() -> R + Send + 'static,
+    ) -> Result<R> {
+        // The slot is held until the work finishes, even if the caller stops waiting
+        let permit = self.module_slots.clone().acquire_owned().await?;
+        // Keeps anything the work logs, or the requests it makes, in the caller's flow
+        let span = tracing::Span::current();
+        let output = tokio::task::spawn_blocking(move || {
+            let _entered = span.enter();
+            let output = work();
+            drop(permit);
+            output
+        })
+        .await?;
+        Ok(output)
+    }
+
+    /// The compiled artifact's location is derived from the module's contents and the
+    /// engine, so a rebuilt module or an upgraded engine never picks up a stale artifact.
+    fn module_cache_path_for_contents(&self, contents: &[u8]) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(self.engine_fingerprint.as_bytes());
+        hasher.update(contents);
+        let last_component = format!("{:x}.wasmtime", hasher.finalize());
         self.cache_dir.join(last_component)
     }
 
     pub async fn fetch_module(&mut self, path: &Path) -> Result<Module> {
         let module = {
-            let cache = self.module_cache.read().await;
-            cache.get(&path.to_path_buf()).cloned()
+            let digests = self.module_digests.read().await;
+            match digests.get(path) {
+                Some(digest) => self.module_cache.read().await.get(digest).cloned(),
+                None => None,
+            }
         };
+        if let Some(module) = module {
+            return Ok(module);
+        }
 
-        match module {
-            Some(module) => Ok(module),
-            None => {
-                let module_cache_path = self.module_cache_path_for_path(path);
-
-                if let Ok(module) = unsafe {
-                    let engine = self.engine.clone();
-                    let module_cache_path = module_cache_path.clone();
-                    tokio::task::spawn_blocking(move || {
-                        Module::deserialize_file(&engine, &module_cache_path)
-                    })
-                    .await?
-                } {
-                    return Ok(module);
-                }
-
-                let contents = match tokio::fs::read(path).await {
-                    Ok(contents) => Ok(contents),
-                    Err(err) => {
-                        println!("Error reading file: {err}");
-                        Err(err)
-                    }
-                }?;
-                let module = Module::new(&self.engine, contents)?;
-
-                let module_ = module.clone();
-                let module_cache_path_ = module_cache_path.clone();
-                tokio::task::spawn_blocking(move || {
-                    if let Ok(bytes) = module_.serialize() {
-                        let _res = std::fs::write(module_cache_path_, bytes);
-                    }
-                })
-                .await?;
-
-                {
-                    let mut cache = self.module_cache.write().await;
-                    cache.insert(path.to_path_buf(), module.clone());
-                }
-                Ok(module)
+        let contents = match tokio::fs::read(path).await {
+            Ok(contents) => contents,
+            Err(err) => {
+                tracing::error!(?path, %err, "Error reading module.");
+                return Err(err.into());
             }
+        };
+        let digest = format!("{:x}", Sha256::digest(&contents));
+        // Another path, or an earlier version of this one, may have had the same contents
+        let module = self.module_cache.read().await.get(&digest).cloned();
+        if let Some(module) = module {
+            self.module_digests
+                .write()
+                .await
+                .insert(path.to_path_buf(), digest);
+            return Ok(module);
+        }
+
+        let module_cache_path = self.module_cache_path_for_contents(&contents);
+        let engine = self.engine.clone();
+        let module = tokio::task::spawn_blocking(move || -> Result<Module> {
+            // Safe since only this runtime writes artifacts, keyed by what they were built from
+            if let Ok(module) = unsafe { Module::deserialize_file(&engine, &module_cache_path) } {
+                return Ok(module);
+            }
+            let module = Module::new(&engine, contents)?;
+            if let Ok(bytes) = module.serialize() {
+                let _res = std::fs::write(module_cache_path, bytes);
+            }
+            Ok(module)
+        })
+        .await??;
+
+        self.module_cache
+            .write()
+            .await
+            .insert(digest.clone(), module.clone());
+        self.module_digests
+            .write()
+            .await
+            .insert(path.to_path_buf(), digest);
+        Ok(module)
+    }
+
+    /// Forgets the module loaded from `path`, so the next request loads it again. The
+    /// compiled module is kept, in case the contents change back or another path shares them.
+    pub async fn invalidate(&self, path: &Path) {
+        tracing::info!(?path, "Module changed, reloading.");
+        self.module_digests.write().await.remove(path);
+        self.instance_cache
+            .write()
+            .await
+            .retain(|(module_path, _), _| module_path != path);
+    }
+}
+
+/// Identifies the compiler and engine settings a compiled artifact is only valid for. Every
+/// serialized module is stamped with both, so an empty one is enough to tell engines apart.
+fn engine_fingerprint(engine: &Engine) -> Result<String> {
+    let artifact = Module::new(engine, "(module)")?.serialize()?;
+    Ok(format!("{:x}", Sha256::digest(&artifact)))
+}
+
+/// Calls the module's default export, falling back to the `_start` function of WASI commands.
+fn call_module<T>(instance_pre: &InstancePre<T>, store: &mut Store<T>) -> Result<()> {
+    let instance = instance_pre.instantiate(&mut *store)?;
+    let entry_point = instance
+        .get_func(&mut *store, "")
+        .or_else(|| instance.get_func(&mut *store, "_start"))
+        .ok_or_else(|| anyhow::Error::msg("Module has no entry point"))?;
+    entry_point
+        .typed::<(), (), _>(&*store)?
+        .call(&mut *store, ())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use tempdir::TempDir;
+
+    use super::WasiRuntime;
+
+    fn write_module(dir: &Path, name: &str, export: &str) -> PathBuf {
+        let path = dir.join(name);
+        let module = format!(r#"(module (func (export "{export}")))"#);
+        std::fs::write(&path, module).expect("should write the module");
+        path
+    }
+
+    #[tokio::test]
+    async fn reloads_rebuilt_modules() {
+        let dir = TempDir::new("proxysaur-reload").expect("should create a temp dir");
+        let mut runtime =
+            WasiRuntime::new(dir.path().to_path_buf()).expect("should build the runtime");
+        let path = write_module(dir.path(), "module.wat", "before");
+        let module = runtime
+            .fetch_module(&path)
+            .await
+            .expect("should load the module");
+        assert!(module.get_export("before").is_some());
+
+        write_module(dir.path(), "module.wat", "after");
+        let module = runtime
+            .fetch_module(&path)
+            .await
+            .expect("should load the module");
+        assert!(
+            module.get_export("before").is_some(),
+            "should keep using the loaded module until it's invalidated"
+        );
+
+        runtime.invalidate(&path).await;
+        let module = runtime
+            .fetch_module(&path)
+            .await
+            .expect("should load the module");
+        assert!(module.get_export("after").is_some());
+    }
+
+    #[tokio::test]
+    async fn shares_modules_with_the_same_contents() {
+        let dir = TempDir::new("proxysaur-shared").expect("should create a temp dir");
+        let mut runtime =
+            WasiRuntime::new(dir.path().to_path_buf()).expect("should build the runtime");
+        for name in ["first.wat", "second.wat"] {
+            let path = write_module(dir.path(), name, "shared");
+            runtime
+                .fetch_module(&path)
+                .await
+                .expect("should load the module");
         }
+        assert_eq!(runtime.module_cache.read().await.len(), 1);
+        assert_eq!(runtime.module_digests.read().await.len(), 2);
+    }
+
+    #[test]
+    fn cache_path_follows_contents() {
+        let runtime = WasiRuntime::new(PathBuf::from("/cache")).expect("should build the runtime");
+        let first = runtime.module_cache_path_for_contents(b"(module)");
+        let second = runtime.module_cache_path_for_contents(b"(module (memory 1))");
+        assert_ne!(first, second);
+        assert_eq!(first, runtime.module_cache_path_for_contents(b"(module)"));
+        assert!(first.starts_with("/cache"));
     }
 }
@@ -1,4 +1,2 @@
 #![allow(clippy::all)]
-mod bindings;
-
-pub use bindings::*;
+wit_bindgen_wasmtime::export!("../import/src/config.wit");
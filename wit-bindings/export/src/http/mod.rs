@@ -1,5 +1,25 @@
 #![allow(clippy::all)]
 #![allow(unused_imports)]
-mod bindings;
+pub mod request {
+    wit_bindgen_wasmtime::export!("../import/src/request.wit");
 
-pub use bindings::*;
+    pub use request::*;
+}
+
+pub mod response {
+    wit_bindgen_wasmtime::export!("../import/src/response.wit");
+
+    pub use response::*;
+}
+
+pub mod pre_request {
+    wit_bindgen_wasmtime::export!("../import/src/pre-request.wit");
+
+    pub use pre_request::*;
+}
+
+pub mod http_client {
+    wit_bindgen_wasmtime::export!("../import/src/http-client.wit");
+
+    pub use http_client::*;
+}
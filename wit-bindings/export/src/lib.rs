@@ -1,2 +1,5 @@
 pub mod config;
 pub mod http;
+pub mod log;
+pub mod pipeline;
+pub mod state;
@@ -0,0 +1,2 @@
+#![allow(clippy::all)]
+wit_bindgen_wasmtime::export!("../import/src/pipeline.wit");
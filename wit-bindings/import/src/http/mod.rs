@@ -15,3 +15,9 @@ pub mod pre_request {
 
     pub use pre_request::*;
 }
+
+pub mod http_client {
+    wit_bindgen_rust::import!("src/http-client.wit");
+
+    pub use http_client::*;
+}
@@ -0,0 +1,3 @@
+wit_bindgen_rust::import!("src/log.wit");
+
+pub use log::*;
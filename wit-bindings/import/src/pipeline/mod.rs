@@ -0,0 +1,3 @@
+wit_bindgen_rust::import!("src/pipeline.wit");
+
+pub use pipeline::*;
@@ -0,0 +1,3 @@
+wit_bindgen_rust::import!("src/state.wit");
+
+pub use state::*;
@@ -5,5 +5,9 @@ fn main() {
 
     if request.authority == "localhost:8000" || request.host == "petermalmgren.com" {
         pre_request::http_set_proxy_mode(ProxyMode::Intercept);
+    } else if request.path == "/blocked" {
+        pre_request::http_block(451).expect("should block the request");
+    } else if request.host == "old.example.com" {
+        pre_request::http_tunnel_to("new.example.com:8080").expect("should tunnel the request");
     }
 }
@@ -1,9 +1,25 @@
-use proxysaur_bindings::http::request::{self, HttpRequest};
+use proxysaur_bindings::http::request::{self, HttpReply, HttpRequest};
+use proxysaur_bindings::state;
 
 fn main() {
     let request: HttpRequest = request::http_request_get().expect("should get the request");
 
     if request.host == "petermalmgren.com" {
+    } else if request.path == "/mock" {
+        request::http_request_respond(HttpReply {
+            status: 418,
+            headers: &[("content-type", "text/plain")],
+            body: "mocked!".as_bytes(),
+        })
+        .expect("should set the response");
+    } else if request.path == "/count" {
+        let count = state::state_increment("count", 1, None).expect("should increment the count");
+        request::http_request_respond(HttpReply {
+            status: 200,
+            headers: &[],
+            body: count.to_string().as_bytes(),
+        })
+        .expect("should set the response");
     } else if request.method.to_lowercase() == "get" {
         request::http_request_set_method("post").expect("should set the method");
         request::http_request_set_body("haha!".as_bytes()).expect("should set the body");